@@ -24,7 +24,12 @@
     const thumbsProgressCompleted = $("#thumbs-progress-completed");
     const thumbsProgressTotal = $("#thumbs-progress-total");
 
+    const renameTagsForm = $("#rename-tags-form");
+    const renameTagsFrom = $("#rename-tags-from");
+    const tagList = $("#admin-tag-list");
+
     let intervalID;
+    let showUnusedOnly = false;
 
     const ALT_PLACEHOLDER = "image caption";
 
@@ -180,6 +185,7 @@
         imageUploadForm.classList.remove("disabled");
         if (this.status === 200) {
             imageList.innerHTML = this.response;
+            showUnusedImages(showUnusedOnly);
             const listEl = imageList.querySelector("ul");
             const total = parseInt(listEl.dataset.initialCount, 10);
             const count = parseInt(listEl.dataset.remaining, 10);
@@ -215,6 +221,16 @@
         }
     }
 
+    // The list is replaced whenever it changes, so the filter has to be reapplied each time
+    function showUnusedImages(show) {
+        showUnusedOnly = show;
+        const checkbox = $("#show-unused-images");
+        if (checkbox) {
+            checkbox.checked = show;
+        }
+        imageList.classList.toggle("unused-only", show && !!checkbox);
+    }
+
     function loadImageList() {
         const xhr = new XMLHttpRequest();
         xhr.addEventListener("load", handleImageListResponse);
@@ -265,6 +281,7 @@
         xhr.addEventListener("load", () => {
             if (xhr.status === 200) {
                 imageList.innerHTML = xhr.response;
+                showUnusedImages(showUnusedOnly);
                 window.location.hash = "";
             } else {
                 alert(`Failed to delete image ${path}`);
@@ -276,6 +293,28 @@
         xhr.send();
     }
 
+    function deleteUnusedImages(count) {
+        if (
+            !confirm(
+                `Are you sure you want to delete all ${count} unused images, along with their thumbnails?\n\nWarning: this cannot be undone.`,
+            )
+        ) {
+            return;
+        }
+        const xhr = new XMLHttpRequest();
+        xhr.addEventListener("load", () => {
+            if (xhr.status === 200) {
+                imageList.innerHTML = xhr.response;
+                showUnusedImages(false);
+            } else {
+                alert("Failed to delete some of the unused images");
+                loadImageList();
+            }
+        });
+        xhr.open("DELETE", "/unused_images");
+        xhr.send();
+    }
+
     function handleThumbClick(el, shift) {
         if (shift) {
             window.open(el.dataset.path, el.fileName);
@@ -292,6 +331,10 @@
                 break;
             }
             case "BUTTON": {
+                if (el.id === "delete-unused-images") {
+                    deleteUnusedImages(el.dataset.count);
+                    break;
+                }
                 const thumb = getAncestor(el, "FIGURE");
                 if (
                     thumb &&
@@ -313,6 +356,12 @@
         }
     });
 
+    imageList.addEventListener("change", (event) => {
+        if (event.target.id === "show-unused-images") {
+            showUnusedImages(event.target.checked);
+        }
+    });
+
     imageUploadForm.addEventListener("submit", (event) => {
         event.preventDefault();
         const xhr = new XMLHttpRequest();
@@ -323,6 +372,41 @@
         setImageListUploading();
     });
 
+    tagList.addEventListener("change", () => {
+        renameTagsFrom.value = $$("#admin-tag-list input:checked")
+            .map((input) => input.value)
+            .join(", ");
+    });
+
+    renameTagsForm.addEventListener("submit", (event) => {
+        event.preventDefault();
+        const data = new URLSearchParams(new FormData(renameTagsForm));
+        if (
+            !confirm(
+                `Rename ${data.get("from")} to ${data.get("to")} in every article that has them?`,
+            )
+        ) {
+            return;
+        }
+
+        const xhr = new XMLHttpRequest();
+        xhr.addEventListener("load", () => {
+            if (xhr.status !== 200) {
+                alert("Error renaming tags");
+                return;
+            }
+            const report = JSON.parse(xhr.responseText);
+            let message = `Updated ${report.updated.length} articles.`;
+            if (report.skipped.length > 0) {
+                message += `\n\nThese still have the old tags, e.g. from their directory, and need editing by hand:\n${report.skipped.join("\n")}`;
+            }
+            alert(message);
+            window.location.reload();
+        });
+        xhr.open(renameTagsForm.method, renameTagsForm.action);
+        xhr.send(data);
+    });
+
     activateTabContent($(".tab.active a").getAttribute("href"));
     loadImageList();
 })(window.document);
@@ -1,8 +1,10 @@
 //=prism
+//=skip-highlighted
 //=exif
 //=captioneer
 //=dimma
 //=exif-popover
 //=theme-selector
 //=comments
+//=search
 
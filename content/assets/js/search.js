@@ -0,0 +1,84 @@
+(function(D) {
+    const forms = D.querySelectorAll('form.search-link, form.search-form');
+    if (forms.length === 0) return;
+
+    // Wait for a pause in typing before searching, so there isn't a request for every key press
+    const TYPING_DELAY = 250;
+    const RESULT_LIMIT = 8;
+
+    function renderResults(list, response) {
+        list.innerHTML = '';
+        if (response.results.length === 0) {
+            const li = D.createElement('li');
+            li.className = 'search-suggestions_empty';
+            li.textContent = 'No articles found';
+            list.appendChild(li);
+            return;
+        }
+        response.results.forEach(result => {
+            const li = D.createElement('li');
+            const a = D.createElement('a');
+            a.href = `/article/${result.slug}`;
+            a.textContent = result.title;
+            const snippet = D.createElement('p');
+            // Snippets are escaped by the server, apart from the <mark> tags around matches
+            snippet.innerHTML = result.snippet;
+            li.appendChild(a);
+            li.appendChild(snippet);
+            list.appendChild(li);
+        });
+        if (response.total > response.results.length) {
+            const li = D.createElement('li');
+            const a = D.createElement('a');
+            a.className = 'search-suggestions_all';
+            a.href = `/search?q=${encodeURIComponent(response.query)}`;
+            a.textContent = `See all ${response.total} results`;
+            li.appendChild(a);
+            list.appendChild(li);
+        }
+    }
+
+    function setupForm(form) {
+        const input = form.querySelector('input[name="q"]');
+        if (!input) return;
+        const list = D.createElement('ul');
+        list.className = 'search-suggestions';
+        list.hidden = true;
+        form.appendChild(list);
+
+        let timeoutID;
+        let xhr;
+
+        function search() {
+            const query = input.value.trim();
+            if (xhr) xhr.abort();
+            if (query === '') {
+                list.hidden = true;
+                return;
+            }
+            xhr = new XMLHttpRequest();
+            xhr.addEventListener('load', () => {
+                // Rate limited or otherwise failed, so the form can still be submitted as normal
+                if (xhr.status !== 200) return;
+                renderResults(list, JSON.parse(xhr.responseText));
+                list.hidden = false;
+            });
+            xhr.open('GET', `/api/search?q=${encodeURIComponent(query)}&limit=${RESULT_LIMIT}`);
+            xhr.send();
+        }
+
+        input.setAttribute('autocomplete', 'off');
+        input.addEventListener('input', () => {
+            clearTimeout(timeoutID);
+            timeoutID = setTimeout(search, TYPING_DELAY);
+        });
+        input.addEventListener('keydown', event => {
+            if (event.key === 'Escape') list.hidden = true;
+        });
+        D.addEventListener('click', event => {
+            if (!form.contains(event.target)) list.hidden = true;
+        });
+    }
+
+    forms.forEach(setupForm);
+}(window.document));
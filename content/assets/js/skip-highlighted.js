@@ -0,0 +1,8 @@
+(function(W) {
+    // Code blocks highlighted when the article was rendered don't need doing again
+    if (!W.Prism) return;
+
+    W.Prism.hooks.add('before-all-elements-highlight', env => {
+        env.elements = env.elements.filter(el => !el.closest('pre[data-highlighted]'));
+    });
+}(window));
@@ -1,4 +1,14 @@
+pub mod aliases;
 pub mod builder;
+pub mod cache;
+pub mod front_matter;
+pub mod headings;
+pub mod highlight;
+pub mod link_check;
+pub mod related;
+pub mod search;
 pub mod storage;
+pub mod tag_rename;
 pub mod view;
+pub mod wiki_links;
 pub use storage::gather_fs_articles;
@@ -0,0 +1,80 @@
+use crate::article::builder::ParsedArticle;
+use crate::config::Config;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SLUG_ALIASES_FILE: &str = "slug_aliases.toml";
+
+// Previous slugs of articles whose titles have been changed, mapped to their current slug, so that
+// old links can be redirected. This is kept up to date automatically, and saved to a file in the
+// content directory, alongside any aliases listed in articles' own front matter.
+#[derive(Clone, Debug)]
+pub struct SlugAliases {
+    aliases: BTreeMap<String, String>,
+    filename: PathBuf,
+}
+
+impl SlugAliases {
+    pub fn load(config: &Config) -> Self {
+        let filename = Path::new(&config.content_dir).join(SLUG_ALIASES_FILE);
+        let aliases = match fs::read_to_string(&filename) {
+            Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+                log::error!("Failed to parse slug aliases from {:?}: {:?}", filename, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+
+        Self { aliases, filename }
+    }
+
+    fn save(&self) {
+        match toml::to_string(&self.aliases) {
+            Ok(s) => {
+                if let Err(e) = fs::write(&self.filename, s) {
+                    log::error!(
+                        "Failed to save slug aliases to {:?}: {:?}",
+                        self.filename,
+                        e
+                    );
+                }
+            }
+            Err(e) => log::error!("Failed to serialize slug aliases: {:?}", e),
+        }
+    }
+
+    /// Records that the article previously at `old_slug` is now at `new_slug`.
+    pub fn record(&mut self, old_slug: &str, new_slug: &str) {
+        // Anything that pointed at the old slug now needs to point at the new one, so there are
+        // no chains of redirects. If the article is being renamed back to a previous slug, that
+        // slug is no longer an alias.
+        for target in self.aliases.values_mut() {
+            if target == old_slug {
+                *target = new_slug.to_string();
+            }
+        }
+        self.aliases.remove(new_slug);
+        self.aliases
+            .insert(old_slug.to_string(), new_slug.to_string());
+        log::info!("Recorded slug alias {old_slug} -> {new_slug}");
+        self.save();
+    }
+
+    /// Finds the current slug of the article that used to be at `slug`, if any. Only articles
+    /// published at `now` count, so drafts and scheduled articles aren't given away by their old
+    /// slugs.
+    pub fn resolve(&self, slug: &str, articles: &[ParsedArticle], now: i64) -> Option<String> {
+        let mut published = articles.iter().filter(|a| a.is_published(now));
+        if let Some(article) = published
+            .clone()
+            .find(|a| a.aliases.iter().any(|s| s == slug))
+        {
+            return Some(article.slug.clone());
+        }
+        self.aliases
+            .get(slug)
+            .filter(|target| published.any(|a| &a.slug == *target))
+            .cloned()
+    }
+}
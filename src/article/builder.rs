@@ -1,15 +1,25 @@
+use crate::article::front_matter::{self, FrontMatter};
+use crate::article::headings::{anchor_headings, TocEntry};
+use crate::article::highlight::highlight_code_blocks;
+use crate::article::wiki_links::{wiki_link_events, wiki_link_text_events};
+use crate::config::{Config, MarkdownConfig};
 use crate::errors::{ParseError, ParseResult};
 use crate::slug::Slug;
+use crate::tags::TagAliases;
 use crate::typography::typogrified;
 use pulldown_cmark::{self as cmark, Event, Tag};
 use regex::Regex;
 use serde::Serialize;
-use std::io::{self, ErrorKind};
+use std::io;
 use std::path::{Path, PathBuf};
 use std::{fmt, fs, time};
 
 const UNIX_EPOCH: time::SystemTime = time::SystemTime::UNIX_EPOCH;
 
+// Edits made within this long after publication don't count as updates, e.g. fixing typos just
+// after posting.
+const MIN_UPDATE_INTERVAL: i64 = 1000 * 60 * 60 * 24;
+
 // See https://stackoverflow.com/questions/38461429/how-can-i-truncate-a-string-to-have-at-most-n-characters
 // String::truncate can panic if the split is not on a char boundary
 fn safe_truncate(s: &str, max_chars: usize) -> &str {
@@ -19,37 +29,107 @@ fn safe_truncate(s: &str, max_chars: usize) -> &str {
     }
 }
 
+/// Extracts the millisecond timestamp from filenames of the form `{slug}{timestamp}.md`, as written
+/// by `storage::create_article`.
+pub fn filename_timestamp(path: &Path) -> Option<i64> {
+    lazy_static! {
+        static ref TRAILING_TIMESTAMP: Regex = Regex::new(r"(\d{13})$").unwrap();
+    }
+    let stem = path.file_stem()?.to_string_lossy();
+    TRAILING_TIMESTAMP
+        .captures(&stem)
+        .and_then(|caps| caps[1].parse().ok())
+}
+
+// Matches the line under a table's header row that sets column alignment, e.g. `|:---|---:|`,
+// which would otherwise have its hyphens turned into dashes if tables are disabled.
+fn is_table_delimiter_row(text: &str) -> bool {
+    lazy_static! {
+        static ref DELIMITER_ROW: Regex =
+            Regex::new(r"^\s*\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)*\|?\s*$").unwrap();
+    }
+    text.contains('|') && DELIMITER_ROW.is_match(text)
+}
+
+// Whether an HTML block is the `<!-- more -->` marker
+fn is_more_marker(html: &str) -> bool {
+    lazy_static! {
+        static ref MORE_MARKER: Regex = Regex::new(r"(?i)^<!--\s*more\s*-->$").unwrap();
+    }
+    MORE_MARKER.is_match(html.trim())
+}
+
 // Struct for creating and managing article data
 pub struct Builder {
     pub content: String,
+    pub front_matter: FrontMatter,
     pub timestamp: i64,
+    pub updated: Option<i64>,
     pub source_filename: PathBuf,
     pub max_preview_length: usize,
+    pub words_per_minute: usize,
+    pub markdown: MarkdownConfig,
+    pub tag_aliases: TagAliases,
+    body_start: usize,
 }
 
 impl Builder {
-    pub fn from_file(path: &Path, max_preview_length: usize) -> Result<Self, io::Error> {
+    // The publication date is taken from the front matter if it has one, falling back to the
+    // given timestamp otherwise. The last updated date only ever comes from the front matter.
+    pub fn new(
+        content: String,
+        timestamp: i64,
+        source_filename: PathBuf,
+        config: &Config,
+        tag_aliases: &TagAliases,
+    ) -> ParseResult<Self> {
+        let (front_matter, body_start) = front_matter::extract(&content)?;
+        let front_matter = front_matter.unwrap_or_default();
+        let timestamp = front_matter.timestamp()?.unwrap_or(timestamp);
+        let updated = front_matter.updated()?;
+        Ok(Self {
+            content,
+            front_matter,
+            timestamp,
+            updated,
+            source_filename,
+            max_preview_length: config.max_preview_length,
+            words_per_minute: config.words_per_minute,
+            markdown: config.markdown.clone(),
+            tag_aliases: tag_aliases.clone(),
+            body_start,
+        })
+    }
+
+    pub fn from_file(path: &Path, config: &Config, tag_aliases: &TagAliases) -> ParseResult<Self> {
         let metadata = fs::metadata(path)?;
         let content = fs::read_to_string(path)?;
         let filedate = metadata.modified()?;
         if let Ok(s) = filedate.duration_since(UNIX_EPOCH) {
-            Ok(Self {
-                content,
-                timestamp: s.as_millis() as i64,
-                source_filename: path.into(),
-                max_preview_length,
-            })
+            // File modified time is only a last resort for the publication date, as it's easily
+            // lost when copying files around.
+            let modified = s.as_millis() as i64;
+            let timestamp = filename_timestamp(path).unwrap_or(modified);
+            Self::new(content, timestamp, path.into(), config, tag_aliases)
         } else {
-            Err(io::Error::new(ErrorKind::Other, "failed to read file"))
+            Err(io::Error::other("failed to read file").into())
         }
     }
 
+    // Article content following the front matter block, if any
+    fn body(&self) -> &str {
+        &self.content[self.body_start..]
+    }
+
     pub fn title(&self) -> ParseResult<String> {
         lazy_static! {
             static ref H1: Regex = Regex::new(r"^#\s*").unwrap();
         }
+        if let Some(title) = &self.front_matter.title {
+            return Ok(title.clone());
+        }
         // Assumes first line of content text is formatted exactly as '# Article Title'
-        self.content
+        self.body()
             .lines()
             .next()
             .map(|l| String::from(H1.replace(l, "")))
@@ -58,12 +138,46 @@ impl Builder {
             })
     }
 
+    // Previous slugs listed in the front matter, which should redirect to this article
+    pub fn aliases(&self) -> Vec<String> {
+        self.front_matter
+            .aliases
+            .iter()
+            .map(|a| Slug::new(a.trim_start_matches("/article/")).into())
+            .collect()
+    }
+
+    // Name of the series the article is part of, if any
+    pub fn series(&self) -> Option<String> {
+        self.front_matter
+            .series
+            .as_ref()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
     pub fn slug(&self) -> ParseResult<Slug> {
+        if let Some(slug) = &self.front_matter.slug {
+            return Ok(Slug::new(slug));
+        }
         Ok(Slug::new(&self.title()?))
     }
 
+    // Number of body lines taken up by the title line, when the title doesn't come from the front
+    // matter
+    fn title_line_count(&self) -> usize {
+        if self.front_matter.title.is_some() {
+            0
+        } else {
+            1
+        }
+    }
+
     fn tags_line(&self) -> Option<String> {
-        if let Some(line) = self.content.lines().nth(1) {
+        if self.front_matter.tags.is_some() {
+            return None;
+        }
+        if let Some(line) = self.body().lines().nth(self.title_line_count()) {
             if line.starts_with('|') && line.ends_with('|') {
                 return Some(line.to_string());
             }
@@ -71,37 +185,84 @@ impl Builder {
         None
     }
 
+    // Tags are folded into the one they're an alias of, if any, so synonyms all end up the same
     pub fn tags(&self) -> Vec<String> {
-        if let Some(line) = self.tags_line() {
-            let mut tags: Vec<String> = line
-                .trim_matches('|')
+        let mut tags: Vec<String> = if let Some(tags) = &self.front_matter.tags {
+            tags.iter().map(|t| Slug::new(t.trim()).into()).collect()
+        } else if let Some(line) = self.tags_line() {
+            line.trim_matches('|')
                 .split(',')
                 .map(|t| Slug::new(t.trim()).into())
-                .collect();
-            tags.sort();
-            tags
+                .collect()
         } else {
             Vec::new()
+        };
+        for tag in tags.iter_mut() {
+            *tag = self.tag_aliases.canonical(tag).to_string();
         }
+        tags.sort();
+        tags.dedup();
+        tags
     }
 
     fn main_content(&self) -> String {
         let skip = match self.tags_line() {
-            Some(_) => 2,
-            None => 1,
+            Some(_) => self.title_line_count() + 1,
+            None => self.title_line_count(),
         };
-        self.content
+        self.body()
             .lines()
             .skip(skip)
             .collect::<Vec<&str>>()
             .join("\n")
     }
 
+    // Splits content at a `<!-- more -->` marker, if there is one, returning the part above it.
+    // Only a marker on its own counts, not one in a code block or in the middle of a paragraph.
+    fn above_more_marker<'a>(&self, content: &'a str) -> Option<&'a str> {
+        let parser = cmark::Parser::new_ext(content, self.markdown.parser_options());
+        let mut depth = 0;
+        for (event, range) in parser.into_offset_iter() {
+            match event {
+                Event::Start(Tag::CodeBlock(_) | Tag::Paragraph) => depth += 1,
+                Event::End(Tag::CodeBlock(_) | Tag::Paragraph) => depth -= 1,
+                Event::Html(html) if depth == 0 && is_more_marker(&html) => {
+                    return Some(&content[..range.start]);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // Uses the front matter summary if there is one, otherwise a truncated plain text version of
+    // the content, or of the part above the `<!-- more -->` marker.
+    pub fn preview(&self) -> String {
+        match &self.front_matter.summary {
+            Some(summary) => typogrified(summary.trim()),
+            None => self.content_preview(self.max_preview_length),
+        }
+    }
+
+    // Rendered HTML of everything above the `<!-- more -->` marker, unless the front matter has a
+    // summary, which takes priority.
+    pub fn preview_html(&self) -> Option<String> {
+        if self.front_matter.summary.is_some() {
+            return None;
+        }
+        let content = self.main_content();
+        let above = self.above_more_marker(&content)?;
+        let mut html = String::new();
+        cmark::html::push_html(&mut html, self.rendered_events(above).into_iter());
+        Some(html)
+    }
+
     pub fn content_preview(&self, max_len: usize) -> String {
         let content = self.main_content();
-        let parser = cmark::Parser::new(&content);
+        let content = self.above_more_marker(&content).unwrap_or(&content);
+        let parser = cmark::Parser::new_ext(content, self.markdown.parser_options());
         let mut parts: Vec<String> = Vec::new();
-        for event in parser {
+        for event in wiki_link_text_events(parser) {
             if let Event::Text(text) = event {
                 parts.push(text.to_string());
                 if parts.len() >= max_len {
@@ -118,11 +279,38 @@ impl Builder {
         }
     }
 
-    pub fn parsed_content(&self) -> String {
+    // Counts the words of prose in the article, leaving out code blocks and images' alt text
+    pub fn word_count(&self) -> usize {
         let content = self.main_content();
-        let parser = cmark::Parser::new(&content);
+        let parser = cmark::Parser::new_ext(&content, self.markdown.parser_options());
+        let mut skip_depth = 0;
+        let mut count = 0;
+        for event in wiki_link_text_events(parser) {
+            match event {
+                Event::Start(Tag::CodeBlock(_) | Tag::Image(..)) => skip_depth += 1,
+                Event::End(Tag::CodeBlock(_) | Tag::Image(..)) => skip_depth -= 1,
+                Event::Text(text) | Event::Code(text) if skip_depth == 0 => {
+                    count += text.split_whitespace().count();
+                }
+                _ => {}
+            }
+        }
+        count
+    }
+
+    // Estimated reading time in minutes, rounded up so that even short articles take a minute
+    pub fn reading_time(&self, word_count: usize) -> usize {
+        let wpm = std::cmp::max(self.words_per_minute, 1);
+        std::cmp::max(word_count.div_ceil(wpm), 1)
+    }
+
+    // Markdown events with wiki links marked, typography applied to the text, and code blocks
+    // highlighted if enabled
+    fn rendered_events<'a>(&self, content: &'a str) -> Vec<Event<'a>> {
+        let parser = cmark::Parser::new_ext(content, self.markdown.parser_options());
+        let events = wiki_link_events(parser);
         let mut in_code_block = false;
-        let typographic_parser = parser.map(|event| match event {
+        let typographic_parser = events.into_iter().map(|event| match event {
             Event::Start(tag) => {
                 if let Tag::CodeBlock(_) = tag {
                     in_code_block = true;
@@ -138,7 +326,7 @@ impl Builder {
                 Event::End(tag)
             }
             Event::Text(text) => {
-                if in_code_block {
+                if in_code_block || is_table_delimiter_row(&text) {
                     Event::Text(text)
                 } else {
                     Event::Text(typogrified(&text).into())
@@ -146,9 +334,35 @@ impl Builder {
             }
             _ => event,
         });
+        if self.markdown.server_highlighting {
+            highlight_code_blocks(typographic_parser)
+        } else {
+            typographic_parser.collect()
+        }
+    }
+
+    // Returns the rendered HTML along with a list of the headings in it
+    pub fn parsed_content(&self) -> (String, Vec<TocEntry>) {
+        let content = self.main_content();
+        let (events, headings) = anchor_headings(
+            self.rendered_events(&content).into_iter(),
+            self.markdown.heading_permalinks,
+        );
         let mut parsed = String::new();
-        cmark::html::push_html(&mut parsed, typographic_parser);
-        parsed
+        cmark::html::push_html(&mut parsed, events.into_iter());
+        (parsed, headings)
+    }
+
+    // Front matter can force the table of contents on or off, otherwise it's down to how many
+    // headings there are.
+    fn wants_toc(&self, heading_count: usize) -> bool {
+        match self.front_matter.toc {
+            Some(toc) => toc,
+            None => {
+                let min = self.markdown.toc_min_headings;
+                min > 0 && heading_count >= min
+            }
+        }
     }
 }
 
@@ -158,6 +372,17 @@ pub struct ArticlePrevNext {
     pub slug: String,
 }
 
+// Where an article sits in its series, for linking to the other parts
+#[derive(Serialize, Clone, Debug)]
+pub struct SeriesNav {
+    pub name: String,
+    pub slug: String,
+    pub position: usize,
+    pub total: usize,
+    pub prev: Option<ArticlePrevNext>,
+    pub next: Option<ArticlePrevNext>,
+}
+
 impl From<&ParsedArticle> for ArticlePrevNext {
     fn from(value: &ParsedArticle) -> Self {
         Self {
@@ -167,37 +392,86 @@ impl From<&ParsedArticle> for ArticlePrevNext {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct ParsedArticle {
     pub title: String,
     pub parsed_content: String,
     pub base_content: String,
     pub preview: String,
+    pub preview_html: Option<String>,
     pub slug: String,
+    pub aliases: Vec<String>,
     pub source_filename: std::path::PathBuf,
     pub timestamp: i64,
+    pub updated: Option<i64>,
     pub tags: Vec<String>,
+    pub summary: Option<String>,
+    pub draft: bool,
+    pub cover_image: Option<String>,
+    pub toc: Vec<TocEntry>,
+    pub word_count: usize,
+    pub reading_time: usize,
     pub comment_count: usize,
     pub prev: Option<ArticlePrevNext>,
     pub next: Option<ArticlePrevNext>,
+    pub series: Option<String>,
+    pub series_slug: Option<String>,
+    pub part: Option<u32>,
+    pub series_nav: Option<SeriesNav>,
+    pub links_to: Vec<String>,
+    pub backlinks: Vec<ArticlePrevNext>,
+    pub related: Vec<ArticlePrevNext>,
+}
+
+impl ParsedArticle {
+    /// Drafts are never published, and articles dated in the future aren't published until that
+    /// time.
+    pub fn is_published(&self, now: i64) -> bool {
+        !self.draft && self.timestamp <= now
+    }
 }
 
 impl TryFrom<&Builder> for ParsedArticle {
     type Error = ParseError;
     fn try_from(b: &Builder) -> Result<Self, Self::Error> {
         let title = b.title()?;
+        let (parsed_content, headings) = b.parsed_content();
+        let toc = if b.wants_toc(headings.len()) {
+            headings
+        } else {
+            Vec::new()
+        };
+        let word_count = b.word_count();
         Ok(ParsedArticle {
             slug: b.slug()?.into(), // borrow here before
-            title,                  // move here
-            parsed_content: b.parsed_content(),
+            aliases: b.aliases(),
+            title, // move here
+            parsed_content,
             base_content: b.content.clone(),
-            preview: b.content_preview(b.max_preview_length),
+            preview: b.preview(),
+            preview_html: b.preview_html(),
             source_filename: b.source_filename.clone(),
             timestamp: b.timestamp,
+            updated: b
+                .updated
+                .filter(|updated| updated - b.timestamp > MIN_UPDATE_INTERVAL),
             tags: b.tags(),
+            summary: b.front_matter.summary.clone(),
+            draft: b.front_matter.draft,
+            cover_image: b.front_matter.cover_image.clone(),
+            toc,
+            word_count,
+            reading_time: b.reading_time(word_count),
             comment_count: 0,
             prev: None,
             next: None,
+            series_slug: b.series().map(|s| Slug::new(&s).into()),
+            series: b.series(),
+            part: b.front_matter.part,
+            series_nav: None,
+            links_to: Vec::new(),
+            backlinks: Vec::new(),
+            related: Vec::new(),
         })
     }
 }
@@ -219,3 +493,57 @@ impl fmt::Display for Builder {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(content: &str) -> Builder {
+        let config = Config {
+            max_preview_length: 100,
+            ..Default::default()
+        };
+        Builder::new(
+            content.to_string(),
+            0,
+            PathBuf::new(),
+            &config,
+            &TagAliases::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn more_marker() {
+        let b = builder("# Title\nIntro para.\n\n<!-- more -->\n\nRest");
+        assert_eq!(b.preview_html().as_deref(), Some("<p>Intro para.</p>\n"));
+        assert_eq!(b.preview(), "Intro para.");
+    }
+
+    #[test]
+    fn more_marker_in_code_block() {
+        let b = builder("# Title\nIntro para.\n\n```\n<!-- more -->\n```\n\nRest");
+        assert_eq!(b.preview_html(), None);
+        assert!(b.preview().ends_with("Rest"));
+    }
+
+    #[test]
+    fn more_marker_in_paragraph() {
+        let b = builder("# Title\nIntro <!-- more --> para.\n\nRest");
+        assert_eq!(b.preview_html(), None);
+    }
+
+    #[test]
+    fn no_more_marker() {
+        let b = builder("# Title\nIntro para.\n\nRest of the article.");
+        assert_eq!(b.preview_html(), None);
+        assert_eq!(b.preview(), "Intro para. Rest of the article.");
+    }
+
+    #[test]
+    fn wiki_links_as_text() {
+        let b = builder("# Title\nSee [[other-post]] and [[Other|the label]].");
+        assert_eq!(b.preview(), "See other-post and the label.");
+        assert_eq!(b.word_count(), 5);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::article::builder::ParsedArticle;
+use crate::tags::TagAliases;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    size: u64,
+    modified: SystemTime,
+    article: ParsedArticle,
+}
+
+// Parsed articles from the last rebuild, keyed on their source file, so that only files that have
+// changed since then need parsing again. A file counts as changed if its size or modified time are
+// different.
+#[derive(Clone, Debug, Default)]
+pub struct ArticleCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    // The aliases the cached articles had their tags folded with
+    tag_aliases: TagAliases,
+}
+
+impl ArticleCache {
+    /// Returns a copy of the cached article for `path`, if the file hasn't changed since.
+    pub fn get(&self, path: &Path, metadata: &fs::Metadata) -> Option<ParsedArticle> {
+        let entry = self.entries.get(path)?;
+        let modified = metadata.modified().ok()?;
+        if entry.size == metadata.len() && entry.modified == modified {
+            Some(entry.article.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, path: &Path, metadata: &fs::Metadata, article: &ParsedArticle) {
+        if let Ok(modified) = metadata.modified() {
+            self.entries.insert(
+                path.to_path_buf(),
+                CacheEntry {
+                    size: metadata.len(),
+                    modified,
+                    article: article.clone(),
+                },
+            );
+        }
+    }
+
+    /// Forgets `path`, so it's parsed again next time even if its size and modified time haven't
+    /// changed, e.g. when an edit keeps the file's original modified time.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.entries.remove(path);
+    }
+
+    /// Forgets everything if the tag aliases have changed since the articles were cached, so all
+    /// the articles are parsed again with the new ones.
+    pub fn use_tag_aliases(&mut self, tag_aliases: &TagAliases) {
+        if self.tag_aliases != *tag_aliases {
+            self.entries.clear();
+            self.tag_aliases = tag_aliases.clone();
+        }
+    }
+
+    /// Drops entries for any files that no longer exist.
+    pub fn retain_paths(&mut self, paths: &HashSet<PathBuf>) {
+        self.entries.retain(|path, _| paths.contains(path));
+    }
+}
@@ -0,0 +1,335 @@
+use crate::errors::{ParseError, ParseResult};
+use chrono::{NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
+
+const TOML_DELIMITER: &str = "+++";
+const YAML_DELIMITER: &str = "---";
+
+// Optional metadata block at the very start of an article, delimited either by `+++` lines (TOML)
+// or `---` lines (YAML). Every field is optional, and anything not given here falls back to the
+// old-style `# Title` and `|tag, tag|` lines.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub slug: Option<String>,
+    pub aliases: Vec<String>,
+    pub date: Option<String>,
+    // When the article was last changed, which is set whenever it's edited via the admin page
+    pub updated: Option<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub tags: Option<Vec<String>>,
+    pub summary: Option<String>,
+    pub draft: bool,
+    pub cover_image: Option<String>,
+    pub toc: Option<bool>,
+    pub series: Option<String>,
+    pub part: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+// Lets a single tag be given on its own, e.g. `tags: rust` in YAML, rather than as a list
+fn one_or_many<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|tags| match tags {
+            OneOrMany::One(tag) => vec![tag],
+            OneOrMany::Many(tags) => tags,
+        }),
+    )
+}
+
+impl FrontMatter {
+    fn from_toml(s: &str) -> ParseResult<Self> {
+        let mut value: toml::Value = toml::from_str(s).map_err(|e| ParseError {
+            cause: format!("invalid TOML front matter: {e}"),
+        })?;
+
+        // Unquoted TOML dates are parsed as a special datetime type rather than a string, so turn
+        // them back into a string for consistency with YAML front matter.
+        if let Some(table) = value.as_table_mut() {
+            for field in ["date", "updated"] {
+                if let Some(toml::Value::Datetime(dt)) = table.get(field) {
+                    let date = dt.to_string();
+                    table.insert(field.to_string(), toml::Value::String(date));
+                }
+            }
+        }
+
+        value.try_into().map_err(|e| ParseError {
+            cause: format!("invalid TOML front matter: {e}"),
+        })
+    }
+
+    fn from_yaml(s: &str) -> ParseResult<Self> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(s).map_err(|e| ParseError {
+            cause: format!("invalid YAML front matter: {e}"),
+        })
+    }
+
+    // Parses the `date` field, if present, into a timestamp in milliseconds.
+    pub fn timestamp(&self) -> ParseResult<Option<i64>> {
+        field_timestamp("date", &self.date)
+    }
+
+    // Parses the `updated` field, if present, into a timestamp in milliseconds.
+    pub fn updated(&self) -> ParseResult<Option<i64>> {
+        field_timestamp("updated", &self.updated)
+    }
+}
+
+fn field_timestamp(field: &str, date: &Option<String>) -> ParseResult<Option<i64>> {
+    match date {
+        Some(date) => parse_date(date).map(Some).ok_or_else(|| ParseError {
+            cause: format!("unable to parse front matter {field} `{date}`"),
+        }),
+        None => Ok(None),
+    }
+}
+
+// Accepts RFC 3339 dates, plus the less strict `YYYY-MM-DD HH:MM[:SS[.fff]]` and plain
+// `YYYY-MM-DD` forms, which are assumed to be UTC.
+pub fn parse_date(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(dt.timestamp_millis());
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(Utc.from_utc_datetime(&dt).timestamp_millis());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| Utc.from_utc_datetime(&dt).timestamp_millis())
+}
+
+/// Sets the `updated` field in the front matter to the given timestamp, replacing any that's
+/// already there. Articles without front matter are given a TOML block with just that field, as
+/// the title and tags lines still work the same after it.
+pub fn with_updated(content: &str, updated: i64) -> String {
+    let date = Utc
+        .timestamp_millis_opt(updated)
+        .single()
+        .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default();
+    let mut lines = content.split_inclusive('\n');
+    let first = lines.next().unwrap_or("");
+    let eol = if first.ends_with("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let (delimiter, separator) = match first.trim_end() {
+        TOML_DELIMITER => (TOML_DELIMITER, '='),
+        YAML_DELIMITER => (YAML_DELIMITER, ':'),
+        _ => return format!("{TOML_DELIMITER}\nupdated = {date}\n{TOML_DELIMITER}\n{content}"),
+    };
+    let field = if separator == '=' {
+        format!("updated = {date}{eol}")
+    } else {
+        format!("updated: {date}{eol}")
+    };
+
+    let mut offset = first.len();
+    for line in lines {
+        let is_updated = line
+            .strip_prefix("updated")
+            .is_some_and(|rest| rest.trim_start().starts_with(separator));
+        if is_updated || line.trim_end() == delimiter {
+            let rest = if is_updated {
+                offset + line.len()
+            } else {
+                offset
+            };
+            return format!("{}{field}{}", &content[..offset], &content[rest..]);
+        }
+        offset += line.len();
+    }
+    // The block is never closed, so it can't be parsed anyway
+    content.to_string()
+}
+
+/// Splits the front matter block, if there is one, from the start of `content`. Returns the parsed
+/// front matter along with the byte offset at which the rest of the article begins.
+pub fn extract(content: &str) -> ParseResult<(Option<FrontMatter>, usize)> {
+    let mut lines = content.split_inclusive('\n');
+    let delimiter = match lines.next().map(str::trim_end) {
+        Some(TOML_DELIMITER) => TOML_DELIMITER,
+        Some(YAML_DELIMITER) => YAML_DELIMITER,
+        _ => return Ok((None, 0)),
+    };
+
+    let block_start = content.find('\n').map(|i| i + 1).unwrap_or(content.len());
+    let mut offset = block_start;
+    for line in lines {
+        if line.trim_end() == delimiter {
+            let block = &content[block_start..offset];
+            let front_matter = if delimiter == TOML_DELIMITER {
+                FrontMatter::from_toml(block)?
+            } else {
+                FrontMatter::from_yaml(block)?
+            };
+            return Ok((Some(front_matter), offset + line.len()));
+        }
+        offset += line.len();
+    }
+
+    Err(ParseError {
+        cause: format!("front matter block opened with `{delimiter}` is never closed"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2023-04-05T06:07:08Z
+    const TIMESTAMP: i64 = 1_680_674_828_000;
+
+    #[test]
+    fn toml_front_matter() {
+        let content =
+            "+++\ntitle = \"Post\"\ntags = [\"rust\", \"web\"]\ndraft = true\n+++\nText\n";
+        let (front_matter, body_start) = extract(content).unwrap();
+        let front_matter = front_matter.unwrap();
+        assert_eq!(front_matter.title.as_deref(), Some("Post"));
+        assert_eq!(front_matter.tags, Some(vec!["rust".into(), "web".into()]));
+        assert!(front_matter.draft);
+        assert_eq!(&content[body_start..], "Text\n");
+    }
+
+    #[test]
+    fn toml_datetimes() {
+        for date in [
+            "2023-04-05T06:07:08Z",
+            "2023-04-05T06:07:08",
+            "2023-04-05 06:07:08",
+        ] {
+            let content = format!("+++\ndate = {date}\n+++\nText\n");
+            let front_matter = extract(&content).unwrap().0.unwrap();
+            assert_eq!(front_matter.timestamp().unwrap(), Some(TIMESTAMP), "{date}");
+        }
+        let content = "+++\ndate = 2023-04-05T06:07:08.250+00:00\n+++\nText\n";
+        let front_matter = extract(content).unwrap().0.unwrap();
+        assert_eq!(front_matter.timestamp().unwrap(), Some(TIMESTAMP + 250));
+    }
+
+    #[test]
+    fn yaml_front_matter() {
+        let content = "---\ntitle: Post\nslug: custom\ntags:\n  - rust\n  - web\n---\nText\n";
+        let (front_matter, body_start) = extract(content).unwrap();
+        let front_matter = front_matter.unwrap();
+        assert_eq!(front_matter.title.as_deref(), Some("Post"));
+        assert_eq!(front_matter.slug.as_deref(), Some("custom"));
+        assert_eq!(front_matter.tags, Some(vec!["rust".into(), "web".into()]));
+        assert_eq!(&content[body_start..], "Text\n");
+    }
+
+    #[test]
+    fn yaml_single_tag() {
+        let content = "---\ntags: rust\n---\nText\n";
+        let front_matter = extract(content).unwrap().0.unwrap();
+        assert_eq!(front_matter.tags, Some(vec!["rust".into()]));
+    }
+
+    #[test]
+    fn empty_front_matter() {
+        let (front_matter, body_start) = extract("---\n---\nText\n").unwrap();
+        assert!(front_matter.unwrap().tags.is_none());
+        assert_eq!(body_start, 8);
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let (front_matter, body_start) = extract("# Title\n|rust|\nText\n").unwrap();
+        assert!(front_matter.is_none());
+        assert_eq!(body_start, 0);
+    }
+
+    #[test]
+    fn unterminated_front_matter() {
+        assert!(extract("+++\ntitle = \"Post\"\nText\n").is_err());
+        assert!(extract("---\ntitle: Post\n").is_err());
+    }
+
+    #[test]
+    fn invalid_front_matter() {
+        assert!(extract("+++\ntitle = \n+++\nText\n").is_err());
+    }
+
+    #[test]
+    fn date_forms() {
+        for (date, expected) in [
+            ("2023-04-05T06:07:08Z", TIMESTAMP),
+            ("2023-04-05T08:07:08+02:00", TIMESTAMP),
+            ("2023-04-05T06:07:08.125Z", TIMESTAMP + 125),
+            ("2023-04-05 06:07:08", TIMESTAMP),
+            ("2023-04-05T06:07:08", TIMESTAMP),
+            ("2023-04-05 06:07:08.5", TIMESTAMP + 500),
+            ("2023-04-05 06:07", TIMESTAMP - 8_000),
+            ("2023-04-05", TIMESTAMP - 22_028_000),
+        ] {
+            assert_eq!(parse_date(date), Some(expected), "{date}");
+        }
+        assert_eq!(parse_date("5th April"), None);
+    }
+
+    #[test]
+    fn updated_field() {
+        let content = "+++\ntitle = \"Post\"\nupdated = 2023-04-05T06:07:08Z\n+++\nText\n";
+        let front_matter = extract(content).unwrap().0.unwrap();
+        assert_eq!(front_matter.updated().unwrap(), Some(TIMESTAMP));
+        let content = "---\nupdated: 2023-04-05 06:07:08\n---\nText\n";
+        let front_matter = extract(content).unwrap().0.unwrap();
+        assert_eq!(front_matter.updated().unwrap(), Some(TIMESTAMP));
+    }
+
+    #[test]
+    fn sets_updated() {
+        assert_eq!(
+            with_updated("+++\ntitle = \"Post\"\n+++\nText\n", TIMESTAMP),
+            "+++\ntitle = \"Post\"\nupdated = 2023-04-05T06:07:08Z\n+++\nText\n"
+        );
+        assert_eq!(
+            with_updated(
+                "---\nupdated: 2020-01-01\ntitle: Post\n---\nText\n",
+                TIMESTAMP
+            ),
+            "---\nupdated: 2023-04-05T06:07:08Z\ntitle: Post\n---\nText\n"
+        );
+        assert_eq!(
+            with_updated("+++\r\nupdated = 2020-01-01\r\n+++\r\nText\r\n", TIMESTAMP),
+            "+++\r\nupdated = 2023-04-05T06:07:08Z\r\n+++\r\nText\r\n"
+        );
+        assert_eq!(
+            with_updated("# Title\n|rust|\nText\n", TIMESTAMP),
+            "+++\nupdated = 2023-04-05T06:07:08Z\n+++\n# Title\n|rust|\nText\n"
+        );
+    }
+
+    #[test]
+    fn invalid_date() {
+        let front_matter = FrontMatter {
+            date: Some("yesterday".into()),
+            ..Default::default()
+        };
+        assert!(front_matter.timestamp().is_err());
+    }
+}
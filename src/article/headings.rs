@@ -0,0 +1,172 @@
+use crate::article::wiki_links::wiki_link_text;
+use crate::slug::Slug;
+use pulldown_cmark::{escape::escape_html, Event, Tag};
+use serde::Serialize;
+use std::collections::HashSet;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TocEntry {
+    pub level: usize,
+    pub title: String,
+    pub id: String,
+}
+
+// Makes sure the same heading text appearing more than once still gives unique IDs, by appending
+// a number to the later ones, skipping any that are already used.
+fn unique_id(text: &str, used: &mut HashSet<String>) -> String {
+    let base: String = Slug::new(text).into();
+    let base = if base.is_empty() {
+        String::from("section")
+    } else {
+        base
+    };
+    let mut id = base.clone();
+    let mut count = 1;
+    while !used.insert(id.clone()) {
+        count += 1;
+        id = format!("{base}-{count}");
+    }
+    id
+}
+
+/// Gives every heading an `id` attribute, based on its text unless it already has one, and
+/// optionally a permalink to itself. Returns the modified events along with a list of the
+/// headings, for building a table of contents.
+pub fn anchor_headings<'a, I>(events: I, permalinks: bool) -> (Vec<Event<'a>>, Vec<TocEntry>)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut output: Vec<Event> = Vec::new();
+    let mut toc: Vec<TocEntry> = Vec::new();
+
+    // IDs given in the Markdown are kept as they are, so generated ones have to avoid them, even
+    // for headings before them
+    let events: Vec<Event> = events.collect();
+    let mut used_ids: HashSet<String> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Start(Tag::Heading(_, Some(id), _)) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    // Heading contents are buffered until the end of the heading, since the ID depends on them
+    let mut heading: Option<(Tag, Vec<Event>)> = None;
+
+    for event in events {
+        match event {
+            Event::Start(tag @ Tag::Heading(..)) => heading = Some((tag, Vec::new())),
+            Event::End(Tag::Heading(..)) => {
+                let (level, id, classes, inner) = match heading.take() {
+                    Some((Tag::Heading(level, id, classes), inner)) => (level, id, classes, inner),
+                    _ => continue,
+                };
+                // Wiki links are still placeholders at this point, so their text is taken from those
+                let title: String = inner
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(t) | Event::Code(t) => Some(t.to_string()),
+                        Event::Html(html) => wiki_link_text(html),
+                        _ => None,
+                    })
+                    .collect();
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => unique_id(&title, &mut used_ids),
+                };
+
+                let mut escaped_id = String::new();
+                escape_html(&mut escaped_id, &id).ok();
+
+                let mut open_tag = format!("<{level} id=\"{escaped_id}\"");
+                if !classes.is_empty() {
+                    open_tag.push_str(" class=\"");
+                    escape_html(&mut open_tag, &classes.join(" ")).ok();
+                    open_tag.push('"');
+                }
+                open_tag.push('>');
+
+                output.push(Event::Html(open_tag.into()));
+                output.extend(inner);
+                if permalinks {
+                    output.push(Event::Html(
+                        format!(
+                            "<a class=\"heading-permalink\" href=\"#{escaped_id}\" aria-label=\"Link to this section\">#</a>"
+                        )
+                        .into(),
+                    ));
+                }
+                output.push(Event::Html(format!("</{level}>\n").into()));
+
+                toc.push(TocEntry {
+                    level: level as usize,
+                    title,
+                    id,
+                });
+            }
+            event => match heading.as_mut() {
+                Some((_, inner)) => inner.push(event),
+                None => output.push(event),
+            },
+        }
+    }
+
+    (output, toc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::article::wiki_links::wiki_link_events;
+    use pulldown_cmark::{Options, Parser};
+
+    fn ids(markdown: &str) -> Vec<String> {
+        let parser = Parser::new_ext(markdown, Options::ENABLE_HEADING_ATTRIBUTES);
+        let (_, toc) = anchor_headings(parser, false);
+        toc.into_iter().map(|entry| entry.id).collect()
+    }
+
+    #[test]
+    fn repeated_headings() {
+        assert_eq!(
+            ids("# Intro\n# Intro\n# Intro\n# \n"),
+            ["intro", "intro-2", "intro-3", "section"]
+        );
+    }
+
+    #[test]
+    fn custom_ids_not_reused() {
+        assert_eq!(
+            ids("# Intro\n# Other {#intro}\n# Setup {#intro-2}\n# Intro\n"),
+            ["intro-3", "intro", "intro-2", "intro-4"]
+        );
+    }
+
+    #[test]
+    fn generated_ids_skip_numbered_headings() {
+        assert_eq!(
+            ids("# Intro 2\n# Intro\n# Intro\n"),
+            ["intro-2", "intro", "intro-3"]
+        );
+    }
+
+    #[test]
+    fn wiki_links_in_headings() {
+        let parser = Parser::new_ext(
+            "## Intro to [[other-post]]\n## [[x|a --> b]]\n",
+            Options::ENABLE_HEADING_ATTRIBUTES,
+        );
+        let (_, toc) = anchor_headings(wiki_link_events(parser).into_iter(), false);
+        let toc: Vec<_> = toc.into_iter().map(|e| (e.title, e.id)).collect();
+        assert_eq!(
+            toc,
+            [
+                (
+                    "Intro to other-post".to_string(),
+                    "intro-to-other-post".to_string()
+                ),
+                ("a --> b".to_string(), "a-b".to_string()),
+            ]
+        );
+    }
+}
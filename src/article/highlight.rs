@@ -0,0 +1,155 @@
+use pulldown_cmark::{escape::escape_html, CodeBlockKind, Event, Tag};
+use syntect::parsing::{BasicScopeStackOp, ParseState, Scope, ScopeStack, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+// Prism token classes paired with the TextMate scopes that best match them, so that server-side
+// highlighting works with the same stylesheets as Prism does. Earlier entries take priority, so
+// more specific scopes need to come before more general ones.
+const TOKEN_SCOPES: &[(&str, &str)] = &[
+    ("comment", "comment"),
+    ("regex", "string.regexp"),
+    ("string", "string"),
+    ("number", "constant.numeric"),
+    ("boolean", "constant.language.boolean"),
+    ("char", "constant.character"),
+    ("constant", "constant"),
+    ("operator", "keyword.operator"),
+    ("keyword", "keyword"),
+    ("keyword", "storage"),
+    ("function", "entity.name.function"),
+    ("function", "support.function"),
+    ("class-name", "entity.name.type"),
+    ("class-name", "entity.name.class"),
+    ("class-name", "support.type"),
+    ("class-name", "support.class"),
+    ("tag", "entity.name.tag"),
+    ("attr-name", "entity.other.attribute-name"),
+    ("property", "support.type.property-name"),
+    ("parameter", "variable.parameter"),
+    ("variable", "variable"),
+    ("punctuation", "punctuation"),
+    ("builtin", "support"),
+    ("inserted", "markup.inserted"),
+    ("deleted", "markup.deleted"),
+    ("bold", "markup.bold"),
+    ("italic", "markup.italic"),
+    ("url", "markup.underline.link"),
+];
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref TOKEN_CLASSES: Vec<(&'static str, Scope)> = TOKEN_SCOPES
+        .iter()
+        .map(|(class, scope)| (*class, Scope::new(scope).unwrap()))
+        .collect();
+}
+
+fn token_class(scope: Scope) -> Option<&'static str> {
+    TOKEN_CLASSES
+        .iter()
+        .find(|(_, s)| s.is_prefix_of(scope))
+        .map(|(class, _)| *class)
+}
+
+// Fenced code block info strings can have more than just the language, e.g. "rust,ignore"
+fn language_token(info: &str) -> &str {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+}
+
+// Returns None if the language isn't recognised, or if the code can't be parsed, in which case
+// the code block should be left as it is.
+fn highlight(code: &str, language: &str) -> Option<String> {
+    let syntax = SYNTAX_SET.find_syntax_by_token(language)?;
+    let mut parse_state = ParseState::new(syntax);
+    let mut scope_stack = ScopeStack::new();
+
+    // Tracks which scopes opened a span, since scopes with no matching token class don't get one
+    let mut open_spans: Vec<bool> = Vec::new();
+
+    let mut escaped_language = String::new();
+    escape_html(&mut escaped_language, language).ok()?;
+    let mut html = format!(
+        "<pre class=\"language-{escaped_language}\" data-highlighted><code class=\"language-{escaped_language}\">"
+    );
+
+    for line in LinesWithEndings::from(code) {
+        let ops = parse_state.parse_line(line, &SYNTAX_SET).ok()?;
+        let mut pos = 0;
+        for (i, op) in ops {
+            escape_html(&mut html, &line[pos..i]).ok()?;
+            pos = i;
+            scope_stack
+                .apply_with_hook(&op, |basic_op, _| match basic_op {
+                    BasicScopeStackOp::Push(scope) => match token_class(scope) {
+                        Some(class) => {
+                            html.push_str("<span class=\"token ");
+                            html.push_str(class);
+                            html.push_str("\">");
+                            open_spans.push(true);
+                        }
+                        None => open_spans.push(false),
+                    },
+                    BasicScopeStackOp::Pop => {
+                        if open_spans.pop().unwrap_or(false) {
+                            html.push_str("</span>");
+                        }
+                    }
+                })
+                .ok()?;
+        }
+        escape_html(&mut html, &line[pos..]).ok()?;
+    }
+
+    for is_span in open_spans {
+        if is_span {
+            html.push_str("</span>");
+        }
+    }
+    html.push_str("</code></pre>\n");
+    Some(html)
+}
+
+/// Replaces fenced code blocks that have a recognised language with pre-highlighted HTML, using
+/// the same classes as Prism would. Anything else is passed through unchanged.
+pub fn highlight_code_blocks<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut output: Vec<Event> = Vec::new();
+
+    // Events making up the current code block, kept in case it can't be highlighted
+    let mut code_block: Option<Vec<Event>> = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                code_block = Some(vec![event]);
+            }
+            Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) if code_block.is_some() => {
+                let mut block = code_block.take().unwrap_or_default();
+                let code: String = block
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(t) => Some(t.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                match highlight(&code, language_token(info)) {
+                    Some(html) => output.push(Event::Html(html.into())),
+                    None => {
+                        block.push(event);
+                        output.extend(block);
+                    }
+                }
+            }
+            event => match code_block.as_mut() {
+                Some(block) => block.push(event),
+                None => output.push(event),
+            },
+        }
+    }
+
+    output
+}
@@ -0,0 +1,145 @@
+use crate::article::aliases::SlugAliases;
+use crate::article::builder::ParsedArticle;
+use crate::redirects::Redirects;
+use crate::tags::TagMetadata;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+
+lazy_static! {
+    // Only links and images pointing at this site's own articles, tags and images are checked.
+    // Anything after a `?` or `#` doesn't affect whether the target exists.
+    static ref INTERNAL_REF: Regex =
+        Regex::new(r#"(?:href|src)="(/(?:article|tag|content/images)/[^"?#]*)"#).unwrap();
+}
+
+/// The internal links and images in an article that don't lead anywhere.
+#[derive(Serialize, Clone, Debug)]
+pub struct BrokenLinks {
+    pub source_title: String,
+    pub source_url: String,
+    pub links: Vec<String>,
+}
+
+// Everything internal links are checked against
+struct Checker<'a> {
+    articles: &'a [ParsedArticle],
+    tags: HashSet<&'a str>,
+    tag_metadata: &'a TagMetadata,
+    slug_aliases: &'a SlugAliases,
+    redirects: &'a Redirects,
+    images_dir: &'a Path,
+    now: i64,
+}
+
+impl Checker<'_> {
+    fn resolves(&self, url: &str) -> bool {
+        // Redirects are followed by the handlers for anything that isn't found, but a 410 means
+        // it's gone on purpose.
+        if let Some((status, _)) = self.redirects.find(url) {
+            return status != 410;
+        }
+        let decoded = percent_decode_str(url).decode_utf8_lossy();
+        if let Some(rest) = decoded.strip_prefix("/article/") {
+            let slug = rest.split('/').next().unwrap_or("");
+            // Drafts and scheduled articles aren't found by anyone but the admin
+            let published = |slug: &str| {
+                self.articles
+                    .iter()
+                    .any(|a| a.slug == slug && a.is_published(self.now))
+            };
+            published(slug)
+                || self
+                    .slug_aliases
+                    .resolve(slug, self.articles, self.now)
+                    .is_some()
+        } else if let Some(rest) = decoded.strip_prefix("/tag/") {
+            let tag = rest.split('/').next().unwrap_or("");
+            self.tags
+                .contains(self.tag_metadata.aliases().canonical(tag))
+        } else if let Some(rest) = decoded.strip_prefix("/content/images/") {
+            // No `..` or absolute paths, so nothing outside the images directory gets looked at
+            let path = Path::new(rest);
+            path.components().all(|c| matches!(c, Component::Normal(_)))
+                && self.images_dir.join(path).is_file()
+        } else {
+            true
+        }
+    }
+
+    fn broken_in(&self, source: &ParsedArticle) -> Vec<String> {
+        let links = INTERNAL_REF
+            .captures_iter(&source.parsed_content)
+            .map(|caps| caps[1].replace("&amp;", "&"));
+        // The cover image is shown with the article too, so counts as one of its images
+        let cover_image = source
+            .cover_image
+            .iter()
+            .filter(|url| url.starts_with("/content/images/"))
+            .cloned();
+        let mut broken: Vec<String> = links
+            .chain(cover_image)
+            .filter(|url| !self.resolves(url))
+            .collect();
+        broken.sort();
+        broken.dedup();
+        broken
+    }
+}
+
+/// Finds internal links to articles and tags that don't exist, articles that aren't published at
+/// `now`, and images that aren't in the images directory, in both articles and pages. External
+/// links aren't checked.
+pub fn check_links(
+    articles: &[ParsedArticle],
+    pages: &[ParsedArticle],
+    tag_metadata: &TagMetadata,
+    slug_aliases: &SlugAliases,
+    redirects: &Redirects,
+    content_dir: &str,
+    now: i64,
+) -> Vec<BrokenLinks> {
+    let images_dir = Path::new(content_dir).join("images");
+    let checker = Checker {
+        articles,
+        // Tag pages only list published articles, and parent tags have pages as long as one of
+        // their children is used
+        tags: articles
+            .iter()
+            .filter(|a| a.is_published(now))
+            .flat_map(|a| a.tags.iter().flat_map(|t| tag_metadata.with_ancestors(t)))
+            .collect(),
+        tag_metadata,
+        slug_aliases,
+        redirects,
+        images_dir: &images_dir,
+        now,
+    };
+
+    let sources = articles
+        .iter()
+        .map(|a| (a, format!("/article/{}", a.slug)))
+        .chain(pages.iter().map(|p| (p, format!("/page/{}", p.slug))));
+    let mut report = Vec::new();
+    for (source, source_url) in sources {
+        let links = checker.broken_in(source);
+        if !links.is_empty() {
+            report.push(BrokenLinks {
+                source_title: source.title.clone(),
+                source_url,
+                links,
+            });
+        }
+    }
+
+    let count: usize = report.iter().map(|b| b.links.len()).sum();
+    if count > 0 {
+        log::warn!(
+            "Found {count} broken internal links or images in {} articles and pages",
+            report.len()
+        );
+    }
+    report
+}
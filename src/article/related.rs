@@ -0,0 +1,139 @@
+use crate::article::builder::{ArticlePrevNext, ParsedArticle};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+// How much shared tags count towards an article's similarity, compared to similar text
+const TAG_WEIGHT: f64 = 0.5;
+
+// Words this short tell us nothing about what an article is about
+const MIN_TERM_LENGTH: usize = 3;
+
+// Common words that are long enough to get past MIN_TERM_LENGTH, but would still make every
+// article look similar to every other
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "but",
+    "can", "could", "did", "does", "doesn", "don", "each", "even", "for", "from", "get", "had",
+    "has", "have", "her", "here", "his", "how", "into", "its", "just", "like", "more", "most",
+    "much", "not", "now", "one", "only", "other", "our", "out", "over", "some", "such", "than",
+    "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "through",
+    "too", "very", "was", "way", "were", "what", "when", "where", "which", "while", "who", "why",
+    "will", "with", "would", "you", "your",
+];
+
+// Term weights for one article, scaled to unit length so the dot product of two of them is their
+// cosine similarity
+type TermVector = HashMap<String, f64>;
+
+lazy_static! {
+    // Code says more about the language it's written in than what the article is about
+    static ref CODE_BLOCK: Regex = Regex::new(r"(?s)<pre[ >].*?</pre>").unwrap();
+    static ref TAG_OR_ENTITY: Regex = Regex::new(r"<[^>]*>|&#?\w+;").unwrap();
+}
+
+fn terms(html: &str) -> Vec<String> {
+    let text = CODE_BLOCK.replace_all(html, " ");
+    let text = TAG_OR_ENTITY.replace_all(&text, " ");
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_TERM_LENGTH)
+        .map(str::to_lowercase)
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+// TF-IDF vectors for each of the articles' text
+fn term_vectors(articles: &[ParsedArticle]) -> Vec<TermVector> {
+    let counts: Vec<HashMap<String, usize>> = articles
+        .iter()
+        .map(|a| {
+            let mut counts = HashMap::new();
+            for term in terms(&a.parsed_content) {
+                *counts.entry(term).or_insert(0) += 1;
+            }
+            counts
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for term in counts.iter().flat_map(|c| c.keys()) {
+        *document_frequency.entry(term).or_insert(0) += 1;
+    }
+
+    let article_count = articles.len() as f64;
+    counts
+        .iter()
+        .map(|counts| {
+            let total: usize = counts.values().sum();
+            let mut vector: TermVector = counts
+                .iter()
+                .map(|(term, &count)| {
+                    let tf = count as f64 / total as f64;
+                    let idf = (article_count / document_frequency[term.as_str()] as f64).ln();
+                    (term.clone(), tf * idf)
+                })
+                .filter(|(_, weight)| *weight > 0.0)
+                .collect();
+            let length = vector.values().map(|w| w * w).sum::<f64>().sqrt();
+            if length > 0.0 {
+                vector.values_mut().for_each(|w| *w /= length);
+            }
+            vector
+        })
+        .collect()
+}
+
+fn cosine_similarity(a: &TermVector, b: &TermVector) -> f64 {
+    let (shorter, longer) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    shorter
+        .iter()
+        .filter_map(|(term, w)| longer.get(term).map(|v| w * v))
+        .sum()
+}
+
+// The proportion of the two articles' tags that they have in common
+fn tag_similarity(a: &HashSet<&String>, b: &HashSet<&String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(b).count() as f64 / union as f64
+    }
+}
+
+/// Works out the `count` most similar published articles to each article, by their tags and
+/// the text they have in common. Ties go to the newer article.
+pub fn set_related_articles(articles: &mut [ParsedArticle], count: usize, now: i64) {
+    let vectors = term_vectors(articles);
+    let tags: Vec<HashSet<&String>> = articles.iter().map(|a| a.tags.iter().collect()).collect();
+    let published: Vec<usize> = (0..articles.len())
+        .filter(|&i| articles[i].is_published(now))
+        .collect();
+
+    let related: Vec<Vec<ArticlePrevNext>> = (0..articles.len())
+        .map(|i| {
+            let mut scores: Vec<(f64, usize)> = published
+                .iter()
+                .filter(|&&j| j != i && articles[j].slug != articles[i].slug)
+                .map(|&j| {
+                    let score = TAG_WEIGHT * tag_similarity(&tags[i], &tags[j])
+                        + (1.0 - TAG_WEIGHT) * cosine_similarity(&vectors[i], &vectors[j]);
+                    (score, j)
+                })
+                .filter(|(score, _)| *score > 0.0)
+                .collect();
+            scores.sort_by(|(sa, a), (sb, b)| {
+                sb.total_cmp(sa)
+                    .then(articles[*b].timestamp.cmp(&articles[*a].timestamp))
+                    .then(articles[*a].slug.cmp(&articles[*b].slug))
+            });
+            scores
+                .iter()
+                .take(count)
+                .map(|&(_, j)| ArticlePrevNext::from(&articles[j]))
+                .collect()
+        })
+        .collect();
+
+    for (article, related) in articles.iter_mut().zip(related) {
+        article.related = related;
+    }
+}
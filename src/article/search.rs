@@ -0,0 +1,355 @@
+use crate::article::builder::ParsedArticle;
+use crate::slug::Slug;
+use crate::tags::TagMetadata;
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+use rust_stemmers::{Algorithm, Stemmer};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+// How much more a match in the title or tags counts for than one in the text
+const TITLE_BOOST: f64 = 3.0;
+const TAGS_BOOST: f64 = 2.0;
+
+// How many words of text to show either side of the first match in a result's snippet
+const SNIPPET_WORDS_BEFORE: usize = 10;
+const SNIPPET_WORDS_AFTER: usize = 20;
+
+lazy_static! {
+    static ref STEMMER: Stemmer = Stemmer::create(Algorithm::English);
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref ENTITY: Regex = Regex::new(r"&(#?\w+);").unwrap();
+    // tag:name, tag:"name with spaces", "a phrase", or just a word
+    static ref QUERY_PART: Regex =
+        Regex::new(r#"(?i)(tag:)?(?:"([^"]*)"?|(\S+))"#).unwrap();
+}
+
+fn stem(word: &str) -> String {
+    STEMMER.stem(&word.to_lowercase()).into_owned()
+}
+
+// Byte ranges of the words in `text`
+fn word_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                ranges.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        ranges.push(s..text.len());
+    }
+    ranges
+}
+
+fn stemmed_words(text: &str) -> Vec<String> {
+    word_ranges(text)
+        .into_iter()
+        .map(|r| stem(&text[r]))
+        .collect()
+}
+
+fn plain_text(html: &str) -> String {
+    let text = TAG.replace_all(html, " ");
+    ENTITY
+        .replace_all(&text, |caps: &regex::Captures| match &caps[1] {
+            "amp" => "&",
+            "lt" => "<",
+            "gt" => ">",
+            "quot" => "\"",
+            "#39" => "'",
+            _ => " ",
+        })
+        .into_owned()
+}
+
+fn escaped(s: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, s).ok();
+    escaped
+}
+
+/// A parsed search query. Every word and phrase has to match for an article to be found, and the
+/// article has to have all the tags.
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    words: Vec<String>,
+    phrases: Vec<Vec<String>>,
+    // Each tag filter, as the set of tags any one of which satisfies it
+    tags: Vec<HashSet<String>>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+        for caps in QUERY_PART.captures_iter(query) {
+            let is_tag = caps.get(1).is_some();
+            let (text, quoted) = match (caps.get(2), caps.get(3)) {
+                (Some(m), _) => (m.as_str(), true),
+                (_, Some(m)) => (m.as_str(), false),
+                _ => continue,
+            };
+            if is_tag {
+                // Written the same way as the articles' tags, e.g. `tag:"Rust Lang"` is `rust-lang`
+                let tag: String = Slug::new(text).into();
+                if !tag.is_empty() {
+                    parsed.tags.push(HashSet::from([tag]));
+                }
+                continue;
+            }
+            let words = stemmed_words(text);
+            if quoted && words.len() > 1 {
+                parsed.phrases.push(words);
+            } else {
+                // Punctuation inside a word, e.g. `don't`, splits it up like in the articles
+                parsed.words.extend(words);
+            }
+        }
+        parsed
+    }
+
+    /// Folds tag filters that are aliases into the tag they're an alias of, and lets them match
+    /// any of its child tags too, the same as the tag pages do.
+    pub fn resolve_tags(mut self, tag_metadata: &TagMetadata) -> Self {
+        for tags in self.tags.iter_mut() {
+            *tags = tags
+                .iter()
+                .flat_map(|t| tag_metadata.with_descendants(tag_metadata.aliases().canonical(t)))
+                .map(String::from)
+                .collect();
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.phrases.is_empty() && self.tags.is_empty()
+    }
+
+    // Every word that needs to match, including those in phrases
+    fn all_words(&self) -> HashSet<&str> {
+        self.words
+            .iter()
+            .chain(self.phrases.iter().flatten())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+// Each article's words are numbered in order, title first, then tags, then the text, with a gap
+// between each so that phrases don't match across them.
+struct IndexedArticle {
+    text: String,
+    text_words: Vec<Range<usize>>,
+    tags: HashSet<String>,
+    tags_start: usize,
+    text_start: usize,
+}
+
+impl IndexedArticle {
+    fn boost(&self, position: usize) -> f64 {
+        if position < self.tags_start {
+            TITLE_BOOST
+        } else if position < self.text_start {
+            TAGS_BOOST
+        } else {
+            1.0
+        }
+    }
+}
+
+/// An article matching a search, and how good a match it is.
+pub struct SearchHit {
+    pub index: usize,
+    pub score: f64,
+}
+
+/// An inverted index of the articles' titles, tags and text, built whenever the articles are
+/// rebuilt. Results refer to articles by their position in the list the index was built from.
+#[derive(Default)]
+pub struct SearchIndex {
+    // Stemmed word -> article index -> positions of the word in the article
+    postings: HashMap<String, HashMap<usize, Vec<usize>>>,
+    articles: Vec<IndexedArticle>,
+}
+
+impl SearchIndex {
+    pub fn new(articles: &[ParsedArticle]) -> Self {
+        let mut index = Self::default();
+        for (i, article) in articles.iter().enumerate() {
+            let title_words = stemmed_words(&article.title);
+            let tag_words: Vec<String> =
+                article.tags.iter().flat_map(|t| stemmed_words(t)).collect();
+            let text = plain_text(&article.parsed_content);
+            let text_words = word_ranges(&text);
+
+            let tags_start = title_words.len() + 1;
+            let text_start = tags_start + tag_words.len() + 1;
+            let positioned = title_words
+                .into_iter()
+                .zip(0..)
+                .chain(tag_words.into_iter().zip(tags_start..))
+                .chain(
+                    text_words
+                        .iter()
+                        .map(|r| stem(&text[r.clone()]))
+                        .zip(text_start..),
+                );
+            for (word, position) in positioned {
+                index
+                    .postings
+                    .entry(word)
+                    .or_default()
+                    .entry(i)
+                    .or_default()
+                    .push(position);
+            }
+
+            index.articles.push(IndexedArticle {
+                text,
+                text_words,
+                tags: article.tags.iter().map(|t| t.to_lowercase()).collect(),
+                tags_start,
+                text_start,
+            });
+        }
+        log::info!(
+            "Indexed {} articles for search, with {} distinct words",
+            index.articles.len(),
+            index.postings.len()
+        );
+        index
+    }
+
+    fn positions(&self, word: &str, article: usize) -> Option<&Vec<usize>> {
+        self.postings.get(word).and_then(|p| p.get(&article))
+    }
+
+    // Positions in the article where the whole phrase starts
+    fn phrase_positions(&self, phrase: &[String], article: usize) -> Vec<usize> {
+        let Some(first) = self.positions(&phrase[0], article) else {
+            return Vec::new();
+        };
+        first
+            .iter()
+            .copied()
+            .filter(|&start| {
+                phrase.iter().enumerate().skip(1).all(|(offset, word)| {
+                    self.positions(word, article)
+                        .map(|p| p.contains(&(start + offset)))
+                        .unwrap_or(false)
+                })
+            })
+            .collect()
+    }
+
+    fn matches(&self, query: &SearchQuery, article: usize) -> bool {
+        query
+            .tags
+            .iter()
+            .all(|tags| !tags.is_disjoint(&self.articles[article].tags))
+            && query
+                .words
+                .iter()
+                .all(|w| self.positions(w, article).is_some())
+            && query
+                .phrases
+                .iter()
+                .all(|p| !self.phrase_positions(p, article).is_empty())
+    }
+
+    // TF-IDF, with matches in the title and tags counting for more
+    fn score(&self, words: &HashSet<&str>, article: usize) -> f64 {
+        let article_count = self.articles.len() as f64;
+        let indexed = &self.articles[article];
+        words
+            .iter()
+            .filter_map(|word| {
+                let postings = self.postings.get(*word)?;
+                let positions = postings.get(&article)?;
+                let frequency: f64 = positions.iter().map(|&p| indexed.boost(p)).sum();
+                let idf = (1.0 + article_count / postings.len() as f64).ln();
+                Some((1.0 + frequency.ln()) * idf)
+            })
+            .sum()
+    }
+
+    /// Some of the article's text around the first match, as HTML with the matching words
+    /// highlighted.
+    pub fn snippet(&self, query: &SearchQuery, article: usize) -> String {
+        let words = query.all_words();
+        let indexed = &self.articles[article];
+        let first_match = query
+            .phrases
+            .iter()
+            .flat_map(|p| self.phrase_positions(p, article))
+            .chain(
+                query
+                    .words
+                    .iter()
+                    .filter_map(|w| self.positions(w, article))
+                    .flatten()
+                    .copied(),
+            )
+            .filter(|&p| p >= indexed.text_start)
+            .min()
+            .map(|p| p - indexed.text_start)
+            .unwrap_or(0);
+
+        let start = first_match.saturating_sub(SNIPPET_WORDS_BEFORE);
+        let end = std::cmp::min(first_match + SNIPPET_WORDS_AFTER, indexed.text_words.len());
+        if start >= end {
+            return String::new();
+        }
+
+        let text = &indexed.text;
+        let ranges = &indexed.text_words[start..end];
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push('…');
+        }
+        let mut last = ranges[0].start;
+        for range in ranges {
+            snippet.push_str(&escaped(&text[last..range.start]));
+            let word = &text[range.clone()];
+            if words.contains(stem(word).as_str()) {
+                snippet.push_str(&format!("<mark>{}</mark>", escaped(word)));
+            } else {
+                snippet.push_str(&escaped(word));
+            }
+            last = range.end;
+        }
+        if end < indexed.text_words.len() {
+            snippet.push('…');
+        }
+        // The text is full of line breaks and indentation from the HTML
+        snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Finds the articles matching the query, best match first. Articles that match equally well,
+    /// e.g. when only searching by tag, stay in their original order. Only articles for which
+    /// `include` returns true are searched.
+    pub fn search<F>(&self, query: &SearchQuery, include: F) -> Vec<SearchHit>
+    where
+        F: Fn(usize) -> bool,
+    {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let words = query.all_words();
+        let mut hits: Vec<SearchHit> = (0..self.articles.len())
+            .filter(|&i| include(i) && self.matches(query, i))
+            .map(|i| SearchHit {
+                index: i,
+                score: self.score(&words, i),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits
+    }
+}
@@ -1,11 +1,19 @@
-use crate::article::builder::{ArticlePrevNext, Builder, ParsedArticle};
+use crate::article::builder::{
+    filename_timestamp, ArticlePrevNext, Builder, ParsedArticle, SeriesNav,
+};
+use crate::article::cache::ArticleCache;
+use crate::article::front_matter;
 use crate::config::Config;
 use crate::errors::{ParseError, ParseResult};
-use crate::io::paths_with_ext_in_dir;
+use crate::handlers::create_timestamp;
+use crate::io::{paths_with_ext_in_dir, paths_with_ext_in_tree};
+use crate::slug::Slug;
+use crate::tags::TagAliases;
 use crate::CommonData;
 use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, ErrorKind};
+use std::io;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
@@ -18,14 +26,18 @@ pub struct PaginatedArticles<'a> {
 pub fn fetch_paginated_articles<'a>(
     page: usize,
     per_page: usize,
-    tag: Option<&str>,
+    tags: Option<&HashSet<&str>>,
     articles: &'a [ParsedArticle],
 ) -> PaginatedArticles<'a> {
-    let article_subset: Vec<&ParsedArticle> = if let Some(t) = tag {
-        let t = t.to_string();
-        articles.iter().filter(|cv| cv.tags.contains(&t)).collect()
+    let now = create_timestamp();
+    let published = articles.iter().filter(|a| a.is_published(now));
+    // A tag's page also includes articles with any of its child tags, so any of them will do
+    let article_subset: Vec<&ParsedArticle> = if let Some(tags) = tags {
+        published
+            .filter(|cv| cv.tags.iter().any(|t| tags.contains(t.as_str())))
+            .collect()
     } else {
-        articles.iter().collect()
+        published.collect()
     };
 
     // Pages are normally provided as 1-indexed from the URL, but page 0 is also valid: it means
@@ -40,9 +52,9 @@ pub fn fetch_paginated_articles<'a>(
         }
     } else {
         if article_subset.is_empty() {
-            log::error!("No articles found. Tag: {tag:?}");
+            log::error!("No articles found. Tags: {tags:?}");
         } else {
-            log::error!("Problem getting page {page} from subset of length {total_articles} with chunk size of {per_page}. Tag {tag:?}");
+            log::error!("Problem getting page {page} from subset of length {total_articles} with chunk size of {per_page}. Tags {tags:?}");
         }
         PaginatedArticles {
             articles: Vec::new(),
@@ -62,28 +74,108 @@ fn fetch_by_slug_mut<'a>(
     articles.iter_mut().find(|a| a.slug == slug)
 }
 
-fn set_prev_next(articles: &mut Vec<ParsedArticle>) {
-    for i in 0..articles.len() {
-        // can't use enumerate because we need to borrow mut within loop
-        let prev = if i > 0 {
-            articles.get(i - 1).map(ArticlePrevNext::from)
+// Links each published article to its neighbours. Unpublished articles are skipped over, and get
+// no links of their own.
+pub fn set_prev_next(articles: &mut [ParsedArticle], now: i64) {
+    let published: Vec<usize> = articles
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.is_published(now))
+        .map(|(i, _)| i)
+        .collect();
+
+    for a in articles.iter_mut() {
+        a.prev = None;
+        a.next = None;
+    }
+
+    for (n, &i) in published.iter().enumerate() {
+        // can't use iter_mut because we need to borrow other articles within loop
+        let prev = if n > 0 {
+            Some(ArticlePrevNext::from(&articles[published[n - 1]]))
         } else {
             None
         };
-        let next = articles.get(i + 1).map(ArticlePrevNext::from);
+        let next = published
+            .get(n + 1)
+            .map(|&j| ArticlePrevNext::from(&articles[j]));
         let a = &mut articles[i];
         a.prev = prev;
         a.next = next;
     }
 }
 
-fn update_article_source(path: &PathBuf, content: &str) -> Result<(), std::io::Error> {
-    let metadata = fs::metadata(path)?;
-    let filedate = match metadata.created() {
-        Ok(c) => c,
-        Err(_) => metadata.modified()?,
-    };
-    let mtime = filetime::FileTime::from_system_time(filedate);
+/// Published articles in the series with the given slug, in order of part number, or publication
+/// date for any without one.
+pub fn fetch_series<'a>(
+    series_slug: &str,
+    articles: &'a [ParsedArticle],
+    now: i64,
+) -> Vec<&'a ParsedArticle> {
+    let mut series: Vec<&ParsedArticle> = articles
+        .iter()
+        .filter(|a| a.is_published(now) && a.series_slug.as_deref() == Some(series_slug))
+        .collect();
+    series.sort_by_key(|a| (a.part.unwrap_or(u32::MAX), a.timestamp));
+    series
+}
+
+// Links each published article in a series to the other parts. This is separate from the
+// chronological links made by `set_prev_next`.
+pub fn set_series_nav(articles: &mut [ParsedArticle], now: i64) {
+    let mut series: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, a) in articles.iter_mut().enumerate() {
+        a.series_nav = None;
+        if let (true, Some(slug)) = (a.is_published(now), &a.series_slug) {
+            series.entry(slug.clone()).or_default().push(i);
+        }
+    }
+
+    for (slug, mut parts) in series {
+        parts.sort_by_key(|&i| (articles[i].part.unwrap_or(u32::MAX), articles[i].timestamp));
+        let name = articles[parts[0]].series.clone().unwrap_or_default();
+        for (n, &i) in parts.iter().enumerate() {
+            let prev = if n > 0 {
+                Some(ArticlePrevNext::from(&articles[parts[n - 1]]))
+            } else {
+                None
+            };
+            let next = parts
+                .get(n + 1)
+                .map(|&j| ArticlePrevNext::from(&articles[j]));
+            articles[i].series_nav = Some(SeriesNav {
+                name: name.clone(),
+                slug: slug.clone(),
+                position: n + 1,
+                total: parts.len(),
+                prev,
+                next,
+            });
+        }
+    }
+}
+
+/// The earliest publication time of any article that's scheduled but not yet published.
+pub fn next_scheduled(articles: &[ParsedArticle], now: i64) -> Option<i64> {
+    articles
+        .iter()
+        .filter(|a| !a.draft && a.timestamp > now)
+        .map(|a| a.timestamp)
+        .min()
+}
+
+fn update_article_source(
+    path: &PathBuf,
+    content: &str,
+    keep_mtime: bool,
+) -> Result<(), std::io::Error> {
+    if !keep_mtime {
+        return fs::write(path, content);
+    }
+
+    // The creation time isn't necessarily the same, e.g. when a file has been copied with its
+    // modified time preserved, so it's the modified time that's kept.
+    let mtime = filetime::FileTime::from_last_modification_time(&fs::metadata(path)?);
     fs::write(path, content)?;
 
     // Modified time needs to be restored to original value to preserve
@@ -100,50 +192,76 @@ pub fn create_article(
         .join(Uuid::new_v4().to_string() + ".md");
     fs::write(&temp_filename, content)?;
 
-    let builder = Builder::from_file(&temp_filename, data.config.max_preview_length)?;
+    let tag_aliases = data.tag_metadata.aliases();
+    let builder = match Builder::from_file(&temp_filename, &data.config, tag_aliases) {
+        Ok(b) => b,
+        Err(e) => {
+            fs::remove_file(&temp_filename)?;
+            return Err(io::Error::other(e.to_string()));
+        }
+    };
     if let Ok(slug) = builder.slug() {
         let new_filename = temp_filename.with_file_name(format!("{slug}{}.md", builder.timestamp));
         log::info!("temp: {:?}, new: {:?}", temp_filename, new_filename);
         if new_filename.is_file() {
-            return Err(io::Error::new(ErrorKind::Other, "File already exists"));
+            return Err(io::Error::other("File already exists"));
         }
         fs::rename(&temp_filename, &new_filename)?;
         builder.try_into().map_err(|e| {
             let msg = format!("Failed to create parsed article frrom builder: {e:?}");
-            io::Error::new(ErrorKind::Other, msg)
+            io::Error::other(msg)
         })
     } else {
-        Err(io::Error::new(
-            ErrorKind::Other,
-            "Couldn't create slug from content",
-        ))
+        Err(io::Error::other("Couldn't create slug from content"))
     }
 }
 
+// Edits are recorded as updates in the front matter, but rewrites that don't change what the
+// article says, like renaming its tags, pass `edited` as false to leave the article as it was.
 pub fn update_article(
     slug: &str,
     new_content: &str,
+    edited: bool,
     data: &mut CommonData,
 ) -> Result<(), std::io::Error> {
     let res = fetch_by_slug_mut(slug, &mut data.articles);
     if let Some(article) = res {
-        let builder = Builder {
-            content: new_content.to_string(),
-            timestamp: article.timestamp,
-            source_filename: article.source_filename.clone(),
-            max_preview_length: data.config.max_preview_length,
+        let new_content = if edited {
+            front_matter::with_updated(new_content, create_timestamp())
+        } else {
+            new_content.to_string()
         };
+        let builder = Builder::new(
+            new_content,
+            article.timestamp,
+            article.source_filename.clone(),
+            &data.config,
+            data.tag_metadata.aliases(),
+        )
+        .map_err(|e| io::Error::other(e.to_string()))?;
 
+        data.article_cache
+            .lock()
+            .invalidate(&article.source_filename);
         if let Ok(new_article) = ParsedArticle::try_from(&builder) {
+            if new_article.slug != slug {
+                data.slug_aliases.record(slug, &new_article.slug);
+            }
             article.base_content = new_article.base_content;
             article.parsed_content = new_article.parsed_content;
             article.preview = new_article.preview;
+            article.preview_html = new_article.preview_html;
+            article.updated = new_article.updated;
             article.tags = new_article.tags;
         }
-        update_article_source(&article.source_filename, &article.base_content)
+        // Only articles with neither a front matter date nor a timestamped filename rely on the
+        // file's modified time for their publication date.
+        let keep_mtime = !edited
+            || builder.front_matter.date.is_none()
+                && filename_timestamp(&article.source_filename).is_none();
+        update_article_source(&article.source_filename, &article.base_content, keep_mtime)
     } else {
-        Err(io::Error::new(
-            ErrorKind::Other,
+        Err(io::Error::other(
             "failed to fetch mutable article reference",
         ))
     }
@@ -153,7 +271,41 @@ pub fn delete_article<P: AsRef<Path>>(source_filename: P) -> Result<(), std::io:
     fs::remove_file(source_filename.as_ref())
 }
 
-pub fn gather_fs_articles(config: &Config) -> ParseResult<Vec<ParsedArticle>> {
+// Tags taken from the subdirectories an article is in, relative to the articles directory, e.g.
+// `articles/travel/2023/post.md` gives `travel` and `2023`.
+fn directory_tags(path: &Path, dir: &Path) -> Vec<String> {
+    let parent = match path.parent().and_then(|p| p.strip_prefix(dir).ok()) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    parent
+        .components()
+        .map(|c| Slug::new(&c.as_os_str().to_string_lossy()).into())
+        .collect()
+}
+
+fn add_directory_tags(
+    article: &mut ParsedArticle,
+    path: &Path,
+    dir: &Path,
+    tag_aliases: &TagAliases,
+) {
+    for tag in directory_tags(path, dir) {
+        let tag = tag_aliases.canonical(&tag).to_string();
+        if !article.tags.contains(&tag) {
+            article.tags.push(tag);
+        }
+    }
+    article.tags.sort();
+}
+
+// Articles can be organised into subdirectories. Those whose files haven't changed since they were
+// last parsed are taken from the cache, which is updated with any that have.
+pub fn gather_fs_articles(
+    config: &Config,
+    tag_aliases: &TagAliases,
+    cache: &mut ArticleCache,
+) -> ParseResult<Vec<ParsedArticle>> {
     let dir = PathBuf::from(&config.content_dir).join("articles");
     if !dir.is_dir() {
         let dir = dir.to_string_lossy();
@@ -163,12 +315,28 @@ pub fn gather_fs_articles(config: &Config) -> ParseResult<Vec<ParsedArticle>> {
     }
 
     let mut articles: Vec<ParsedArticle> = Vec::new();
+    let mut seen_paths: HashSet<PathBuf> = HashSet::new();
+    let mut parsed_count = 0;
+
+    paths_with_ext_in_tree("md", &dir, |path| {
+        seen_paths.insert(path.to_path_buf());
+        let metadata = fs::metadata(path).ok();
+        if let Some(article) = metadata.as_ref().and_then(|m| cache.get(path, m)) {
+            articles.push(article);
+            return;
+        }
 
-    paths_with_ext_in_dir("md", &dir, |path| {
         log::debug!("Building article from {}", path.to_string_lossy());
-        match Builder::from_file(path, config.max_preview_length) {
+        parsed_count += 1;
+        match Builder::from_file(path, config, tag_aliases) {
             Ok(builder) => {
-                if let Ok(article) = ParsedArticle::try_from(&builder) {
+                if let Ok(mut article) = ParsedArticle::try_from(&builder) {
+                    if config.directory_tags {
+                        add_directory_tags(&mut article, path, &dir, tag_aliases);
+                    }
+                    if let Some(metadata) = &metadata {
+                        cache.insert(path, metadata, &article);
+                    }
                     articles.push(article);
                 } else {
                     log::error!("Failed to convert builder:\n{}\nto article", &builder);
@@ -187,8 +355,47 @@ pub fn gather_fs_articles(config: &Config) -> ParseResult<Vec<ParsedArticle>> {
         }
     });
 
+    cache.retain_paths(&seen_paths);
+    log::info!(
+        "Gathered {} articles, {} of them parsed from changed files",
+        articles.len(),
+        parsed_count
+    );
+
     articles.sort_by_key(|k| k.timestamp);
     articles.reverse();
-    set_prev_next(&mut articles);
+    let now = create_timestamp();
+    set_prev_next(&mut articles, now);
+    set_series_nav(&mut articles, now);
     Ok(articles)
 }
+
+/// Builds standalone pages from the `pages` directory, if there is one. Pages aren't dated and
+/// aren't linked to each other, so are simply sorted by filename.
+pub fn gather_fs_pages(config: &Config) -> Vec<ParsedArticle> {
+    let dir = PathBuf::from(&config.content_dir).join("pages");
+    if !dir.is_dir() {
+        return Vec::new();
+    }
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    paths_with_ext_in_dir("md", &dir, |path| paths.push(path.to_path_buf()));
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            // Pages aren't listed by tag, so there's nothing to fold their tags into
+            let page = Builder::from_file(path, config, &TagAliases::default())
+                .and_then(|builder| ParsedArticle::try_from(&builder));
+            if let Err(e) = &page {
+                log::error!(
+                    "Failed to build page from {}: {:?}",
+                    path.to_string_lossy(),
+                    e
+                );
+            }
+            page.ok()
+        })
+        .collect()
+}
@@ -0,0 +1,269 @@
+use crate::article::builder::Builder;
+use crate::article::front_matter;
+use crate::article::storage::update_article;
+use crate::commondata::rebuild;
+use crate::errors::ParseResult;
+use crate::slug::Slug;
+use crate::tags::TagAliases;
+use crate::{CommonData, SharedData};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::HashSet;
+
+lazy_static! {
+    // `tags = [...]` in TOML front matter, or `tags: [...]` in YAML
+    static ref INLINE_TAGS: Regex =
+        Regex::new(r"(?m)^(\s*tags\s*[=:]\s*\[)([^\]\n]*)(\][ \t\r]*)$").unwrap();
+    // `tags:` in YAML front matter, followed by a `- tag` line for each tag
+    static ref YAML_TAG_LIST: Regex =
+        Regex::new(r"(?m)^tags:[ \t\r]*\n((?:[ \t]*-[^\n]*\n)+)").unwrap();
+    static ref YAML_LIST_ITEM: Regex = Regex::new(r"^([ \t]*-[ \t]*)(.*?)([ \t\r]*)$").unwrap();
+}
+
+/// What happened to each article with one of the renamed tags.
+#[derive(Serialize, Debug, Default)]
+pub struct TagRenameReport {
+    pub updated: Vec<String>,
+    // Articles that still have the tag, e.g. because it comes from their directory
+    pub skipped: Vec<String>,
+}
+
+struct Renamer<'a> {
+    from: &'a HashSet<String>,
+    to: &'a str,
+    aliases: &'a TagAliases,
+}
+
+impl Renamer<'_> {
+    fn matches(&self, tag: &str) -> bool {
+        let slug: String = Slug::new(tag.trim()).into();
+        self.from.contains(self.aliases.canonical(&slug))
+    }
+
+    // Renames the tags in a comma-separated list, keeping any quotes around them. Merging tags can
+    // leave the same tag in the list twice, so only the first is kept.
+    fn rename_list(&self, list: &str) -> String {
+        let mut seen = HashSet::new();
+        list.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| self.rename_item(item))
+            .filter(|item| seen.insert(Slug::new(unquoted(item)).to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn rename_item(&self, item: &str) -> String {
+        let tag = unquoted(item);
+        if !self.matches(tag) {
+            return item.to_string();
+        }
+        match item.chars().next() {
+            Some(q @ ('"' | '\'')) if tag.len() < item.len() => format!("{q}{}{q}", self.to),
+            _ => self.to.to_string(),
+        }
+    }
+
+    fn rename_yaml_list(&self, list: &str) -> String {
+        let mut seen = HashSet::new();
+        let mut renamed = String::new();
+        for line in list.lines() {
+            let Some(caps) = YAML_LIST_ITEM.captures(line) else {
+                continue;
+            };
+            let item = self.rename_item(&caps[2]);
+            if seen.insert(Slug::new(unquoted(&item)).to_string()) {
+                renamed.push_str(&format!("{}{item}{}\n", &caps[1], &caps[3]));
+            }
+        }
+        renamed
+    }
+}
+
+fn unquoted(item: &str) -> &str {
+    item.trim_matches(|c| c == '"' || c == '\'')
+}
+
+// Rewrites the article's tags where they're written in its source, which is either the front
+// matter or the `|tag, tag|` line after the title, leaving everything else as it was.
+fn renamed_source(content: &str, renamer: &Renamer) -> Option<String> {
+    let (front_matter, body_start) = front_matter::extract(content).ok()?;
+    let front_matter = front_matter.unwrap_or_default();
+
+    if front_matter.tags.is_some() {
+        let (block, body) = content.split_at(body_start);
+        let block = INLINE_TAGS.replace(block, |caps: &Captures| {
+            format!("{}{}{}", &caps[1], renamer.rename_list(&caps[2]), &caps[3])
+        });
+        let block = YAML_TAG_LIST.replace(&block, |caps: &Captures| {
+            format!("tags:\n{}", renamer.rename_yaml_list(&caps[1]))
+        });
+        return Some(format!("{block}{body}"));
+    }
+
+    // Same as `Builder::tags_line`, but keeping track of where the line is
+    let title_lines = if front_matter.title.is_some() { 0 } else { 1 };
+    let mut offset = body_start;
+    for (n, line) in content[body_start..].split_inclusive('\n').enumerate() {
+        if n == title_lines {
+            let trimmed = line.trim_end_matches(['\r', '\n']);
+            if !(trimmed.starts_with('|') && trimmed.ends_with('|')) {
+                return None;
+            }
+            let list = renamer.rename_list(trimmed.trim_matches('|'));
+            let end = offset + trimmed.len();
+            return Some(format!("{}|{list}|{}", &content[..offset], &content[end..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Renames the given tags to `to` in every article that has any of them, which merges them if
+/// there's more than one. Articles are updated the same way as when edited, so they keep their
+/// timestamps, and the index is rebuilt once at the end.
+pub fn rename_tags(
+    from: &HashSet<String>,
+    to: &str,
+    data: &SharedData,
+) -> ParseResult<TagRenameReport> {
+    let report = rename_in_sources(from, to, &mut data.write());
+    log::info!(
+        "Renamed tags {from:?} to {to} in {} articles, skipped {}",
+        report.updated.len(),
+        report.skipped.len()
+    );
+    if !report.updated.is_empty() {
+        rebuild(data)?;
+    }
+    Ok(report)
+}
+
+fn rename_in_sources(from: &HashSet<String>, to: &str, data: &mut CommonData) -> TagRenameReport {
+    let mut report = TagRenameReport::default();
+    let affected: Vec<(String, String, String)> = data
+        .articles
+        .iter()
+        .filter(|a| a.tags.iter().any(|t| from.contains(t)))
+        .map(|a| (a.slug.clone(), a.title.clone(), a.base_content.clone()))
+        .collect();
+
+    for (slug, title, content) in affected {
+        let renamer = Renamer {
+            from,
+            to,
+            aliases: data.tag_metadata.aliases(),
+        };
+        let new_content = renamed_source(&content, &renamer)
+            .filter(|c| *c != content)
+            .filter(|c| still_tagged(c, from, data).is_some_and(|tagged| !tagged));
+        let Some(new_content) = new_content else {
+            log::warn!("Couldn't rename tags in '{slug}', as they're not in its source");
+            report.skipped.push(title);
+            continue;
+        };
+        match update_article(&slug, &new_content, false, data) {
+            Ok(_) => report.updated.push(title),
+            Err(e) => {
+                log::error!("Failed to rename tags in '{slug}': {e:?}");
+                report.skipped.push(title);
+            }
+        }
+    }
+    report
+}
+
+// Whether the rewritten source still has any of the old tags, in case they're written in a way
+// that isn't recognised, e.g. a TOML array split over several lines
+fn still_tagged(content: &str, from: &HashSet<String>, data: &CommonData) -> Option<bool> {
+    let builder = Builder::new(
+        content.to_string(),
+        0,
+        Default::default(),
+        &data.config,
+        data.tag_metadata.aliases(),
+    )
+    .ok()?;
+    Some(builder.tags().iter().any(|t| from.contains(t)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(content: &str, from: &[&str], to: &str) -> Option<String> {
+        let from: HashSet<String> = from.iter().map(|t| t.to_string()).collect();
+        let aliases = TagAliases::default();
+        let renamer = Renamer {
+            from: &from,
+            to,
+            aliases: &aliases,
+        };
+        renamed_source(content, &renamer)
+    }
+
+    #[test]
+    fn renames_toml_tags_keeping_quotes() {
+        let content = "+++\ntitle = \"Post\"\ntags = [\"Old Tag\", 'other']\n+++\nText\n";
+        assert_eq!(
+            rename(content, &["old-tag"], "new").as_deref(),
+            Some("+++\ntitle = \"Post\"\ntags = [\"new\", 'other']\n+++\nText\n")
+        );
+    }
+
+    #[test]
+    fn merges_toml_tags() {
+        let content = "+++\ntags = [\"Old Tag\", 'other', \"keep\"]\n+++\nText\n";
+        assert_eq!(
+            rename(content, &["old-tag", "other"], "new").as_deref(),
+            Some("+++\ntags = [\"new\", \"keep\"]\n+++\nText\n")
+        );
+    }
+
+    #[test]
+    fn renames_yaml_flow_list() {
+        let content = "---\ntitle: Post\ntags: [other, misc]\n---\nText\n";
+        assert_eq!(
+            rename(content, &["other"], "new").as_deref(),
+            Some("---\ntitle: Post\ntags: [new, misc]\n---\nText\n")
+        );
+    }
+
+    #[test]
+    fn renames_yaml_block_list() {
+        let content = "---\ntitle: Post\ntags:\n  - Other\n  - misc\n  - \"new\"\n---\nText\n";
+        assert_eq!(
+            rename(content, &["other"], "new").as_deref(),
+            Some("---\ntitle: Post\ntags:\n  - new\n  - misc\n---\nText\n")
+        );
+    }
+
+    #[test]
+    fn renames_tags_line() {
+        let content = "Title\n|old-tag, misc|\nText |not, tags|\n";
+        assert_eq!(
+            rename(content, &["old-tag"], "new").as_deref(),
+            Some("Title\n|new, misc|\nText |not, tags|\n")
+        );
+    }
+
+    #[test]
+    fn renames_tags_line_with_crlf() {
+        let content = "Title\r\n|old-tag, misc|\r\nText\r\n";
+        assert_eq!(
+            rename(content, &["old-tag"], "new").as_deref(),
+            Some("Title\r\n|new, misc|\r\nText\r\n")
+        );
+    }
+
+    #[test]
+    fn no_tags_line() {
+        assert_eq!(rename("Title\nText\n", &["old-tag"], "new"), None);
+    }
+
+    #[test]
+    fn leaves_multi_line_toml_array() {
+        let content = "+++\ntags = [\n  \"old\",\n]\n+++\nText\n";
+        assert_eq!(rename(content, &["old"], "new").as_deref(), Some(content));
+    }
+}
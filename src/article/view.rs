@@ -1,13 +1,16 @@
 use crate::{
-    article::storage::{fetch_by_slug, PaginatedArticles},
+    article::storage::PaginatedArticles,
     comments::{Comment, Comments},
     CommonData,
 };
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use pulldown_cmark::escape::escape_html;
 use regex::Regex;
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
 
-use super::builder::ParsedArticle;
+use super::builder::{ArticlePrevNext, ParsedArticle, SeriesNav};
+use super::search::{SearchHit, SearchQuery};
+use crate::tags::{TagLink, TagSummary};
 
 #[derive(Serialize)]
 pub struct IndexRenderView<'a> {
@@ -18,6 +21,10 @@ pub struct IndexRenderView<'a> {
     next_page: usize,
     last_page: usize,
     search_tag: Option<&'a str>,
+    tag_name: Option<&'a str>,
+    tag_description: Option<&'a str>,
+    parent_tag: Option<TagLink<'a>>,
+    child_tags: Vec<TagLink<'a>>,
     article_count: usize,
     articles: Vec<&'a ParsedArticle>,
     comment_counts: Vec<usize>,
@@ -38,8 +45,16 @@ impl<'a> IndexRenderView<'a> {
     ) -> Self {
         let last_page = div_ceil(article_list.total_articles, page_size);
 
-        let title = if let Some(tag) = tag {
-            String::from("Tag: ") + tag
+        let tag_name = tag.map(|t| data.tag_metadata.display_name(t));
+        let tag_description = tag
+            .and_then(|t| data.tag_metadata.get(t))
+            .and_then(|info| info.description.as_deref());
+
+        // Titles are output unescaped, since article titles can contain HTML
+        let title = if let Some(name) = tag_name {
+            let mut title = String::from("Tag: ");
+            escape_html(&mut title, name).ok();
+            title
         } else {
             String::from("Article Index")
         };
@@ -56,12 +71,23 @@ impl<'a> IndexRenderView<'a> {
         Self {
             blog_title: &data.config.blog_title,
             title,
-            prev_page: if page > 1 { page - 1 } else { 0 },
+            prev_page: page.saturating_sub(1),
             current_page: page,
             next_page: if page < last_page { page + 1 } else { 0 },
             last_page,
             body_class: if tag.is_some() { "tag-index" } else { "index" },
             search_tag: tag,
+            tag_name,
+            tag_description,
+            parent_tag: tag
+                .and_then(|t| data.tag_metadata.parent(t))
+                .map(|p| data.tag_metadata.link(p)),
+            child_tags: tag
+                .map(|t| data.tag_metadata.children(t))
+                .unwrap_or_default()
+                .iter()
+                .map(|c| data.tag_metadata.link(c))
+                .collect(),
             article_count: article_list.total_articles,
             articles: article_list.articles.clone(), // is a vec of refs, so clone is cheap
             comment_counts: Self::get_comment_counts(&article_list.articles, &data.comments),
@@ -90,6 +116,118 @@ fn div_ceil(lhs: usize, rhs: usize) -> usize {
     }
 }
 
+#[derive(Serialize)]
+pub struct SearchResultView<'a> {
+    article: &'a ParsedArticle,
+    snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct SearchRenderView<'a> {
+    blog_title: &'a str,
+    title: String,
+    query: &'a str,
+    // The query again, encoded for use in the pagination links
+    query_param: String,
+    prev_page: usize,
+    current_page: usize,
+    next_page: usize,
+    last_page: usize,
+    result_count: usize,
+    results: Vec<SearchResultView<'a>>,
+    body_class: &'a str,
+    content_dir: &'a str,
+    theme: String,
+}
+
+impl<'a> SearchRenderView<'a> {
+    pub fn new(
+        query: &'a str,
+        hits: &[SearchHit],
+        page: usize,
+        theme: String,
+        data: &'a CommonData,
+    ) -> Self {
+        let page_size = data.config.page_size;
+        let last_page = div_ceil(hits.len(), page_size);
+        let page = std::cmp::max(page, 1);
+        let parsed_query = SearchQuery::parse(query);
+        let results = hits
+            .chunks(page_size)
+            .nth(page - 1)
+            .unwrap_or_default()
+            .iter()
+            .map(|hit| SearchResultView {
+                article: &data.articles[hit.index],
+                snippet: data.search_index.snippet(&parsed_query, hit.index),
+            })
+            .collect();
+
+        Self {
+            blog_title: &data.config.blog_title,
+            title: if query.is_empty() {
+                String::from("Search")
+            } else {
+                let mut title = String::from("Search: ");
+                escape_html(&mut title, query).ok();
+                title
+            },
+            query,
+            query_param: utf8_percent_encode(query, NON_ALPHANUMERIC).to_string(),
+            prev_page: page.saturating_sub(1),
+            current_page: page,
+            next_page: if page < last_page { page + 1 } else { 0 },
+            last_page,
+            result_count: hits.len(),
+            results,
+            body_class: "search",
+            content_dir: &data.config.content_dir,
+            theme,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiSearchResult<'a> {
+    title: &'a str,
+    slug: &'a str,
+    snippet: String,
+    tags: &'a [String],
+    timestamp: i64,
+}
+
+#[derive(Serialize)]
+pub struct ApiSearchView<'a> {
+    query: &'a str,
+    total: usize,
+    results: Vec<ApiSearchResult<'a>>,
+}
+
+impl<'a> ApiSearchView<'a> {
+    pub fn new(query: &'a str, hits: &[SearchHit], limit: usize, data: &'a CommonData) -> Self {
+        let parsed_query = SearchQuery::parse(query);
+        let results = hits
+            .iter()
+            .take(limit)
+            .map(|hit| {
+                let article = &data.articles[hit.index];
+                ApiSearchResult {
+                    title: &article.title,
+                    slug: &article.slug,
+                    snippet: data.search_index.snippet(&parsed_query, hit.index),
+                    tags: &article.tags,
+                    timestamp: article.timestamp,
+                }
+            })
+            .collect();
+        Self {
+            query,
+            total: hits.len(),
+            results,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct RssArticleView<'a> {
     title: &'a str,
@@ -130,15 +268,14 @@ pub struct RssIndexView<'a> {
     pub articles: Vec<RssArticleView<'a>>,
 }
 
-const MAX_RELATED_ARTICLES: usize = 5;
-
 #[derive(Serialize)]
 pub struct ArticleRenderView<'a> {
     title: &'a str,
     //blog_title: String,
     blog_title: &'a str,
     article: &'a ParsedArticle,
-    related_articles: Vec<&'a ParsedArticle>,
+    related_articles: &'a [ArticlePrevNext],
+    series: Option<&'a SeriesNav>,
     comments: Option<&'a Vec<Comment>>,
     return_path: &'a str,
     body_class: &'a str,
@@ -149,7 +286,6 @@ pub struct ArticleRenderView<'a> {
 impl<'a> ArticleRenderView<'a> {
     pub fn new(
         article: &'a ParsedArticle,
-        all_articles: &'a [ParsedArticle],
         return_path: &'a str,
         theme: &'a str,
         data: &'a CommonData,
@@ -159,7 +295,8 @@ impl<'a> ArticleRenderView<'a> {
             blog_title: &data.config.blog_title,
             comments: data.comments.get_for(&article.slug),
             article,
-            related_articles: related_articles(article, all_articles),
+            related_articles: &article.related,
+            series: article.series_nav.as_ref(),
             return_path,
             body_class: "article",
             content_dir: &data.config.content_dir,
@@ -168,35 +305,84 @@ impl<'a> ArticleRenderView<'a> {
     }
 }
 
-fn related_articles<'a>(
-    article: &'a ParsedArticle,
-    all_articles: &'a [ParsedArticle],
-) -> Vec<&'a ParsedArticle> {
-    let mut related_slugs: HashMap<&String, usize> = HashMap::new();
-    let a_tags: HashSet<&String> = HashSet::from_iter(article.tags.iter());
-
-    for b in all_articles {
-        let common_tag_count = HashSet::from_iter(b.tags.iter())
-            .intersection(&a_tags)
-            .count();
-        if common_tag_count > 0 && article.slug != b.slug {
-            related_slugs.insert(&b.slug, common_tag_count);
+#[derive(Serialize)]
+pub struct PageRenderView<'a> {
+    title: &'a str,
+    blog_title: &'a str,
+    page: &'a ParsedArticle,
+    body_class: &'a str,
+    content_dir: &'a str,
+    theme: &'a str,
+}
+
+impl<'a> PageRenderView<'a> {
+    pub fn new(page: &'a ParsedArticle, theme: &'a str, data: &'a CommonData) -> Self {
+        Self {
+            title: &page.title,
+            blog_title: &data.config.blog_title,
+            page,
+            body_class: "page",
+            content_dir: &data.config.content_dir,
+            theme,
         }
     }
+}
 
-    let mut related_slugs: Vec<(String, usize)> = related_slugs
-        .iter()
-        .map(|kv| {
-            let key = String::from(&**kv.0);
-            (key, *kv.1)
-        })
-        .collect();
-
-    related_slugs.sort_by(|a, b| b.1.cmp(&a.1));
-    related_slugs
-        .iter()
-        .map(|kv| fetch_by_slug(&kv.0, all_articles).unwrap()) // unwrap is fine here; we know the
-        // article exists
-        .take(MAX_RELATED_ARTICLES)
-        .collect()
+#[derive(Serialize)]
+pub struct SeriesRenderView<'a> {
+    title: &'a str,
+    blog_title: &'a str,
+    series_slug: &'a str,
+    article_count: usize,
+    articles: Vec<&'a ParsedArticle>,
+    body_class: &'a str,
+    content_dir: &'a str,
+    theme: &'a str,
+}
+
+impl<'a> SeriesRenderView<'a> {
+    pub fn new(
+        series_slug: &'a str,
+        articles: Vec<&'a ParsedArticle>,
+        theme: &'a str,
+        data: &'a CommonData,
+    ) -> Self {
+        Self {
+            // Articles are only listed here if they're in the series, so there's always a name
+            title: articles[0].series.as_deref().unwrap_or(series_slug),
+            blog_title: &data.config.blog_title,
+            series_slug,
+            article_count: articles.len(),
+            articles,
+            body_class: "series",
+            content_dir: &data.config.content_dir,
+            theme,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TagsRenderView<'a> {
+    title: &'a str,
+    blog_title: &'a str,
+    tag_count: usize,
+    tags: Vec<TagSummary<'a>>,
+    body_class: &'a str,
+    content_dir: &'a str,
+    theme: &'a str,
+}
+
+impl<'a> TagsRenderView<'a> {
+    pub fn new(articles: &[&'a ParsedArticle], theme: &'a str, data: &'a CommonData) -> Self {
+        let tags = data.tag_metadata.summarize(articles);
+        Self {
+            title: "Tags",
+            blog_title: &data.config.blog_title,
+            tag_count: tags.len(),
+            tags,
+            body_class: "tags",
+            content_dir: &data.config.content_dir,
+            theme,
+        }
+    }
 }
@@ -0,0 +1,291 @@
+use crate::article::builder::{ArticlePrevNext, ParsedArticle};
+use crate::slug::Slug;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use pulldown_cmark::{escape::escape_html, Event, Tag};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Wiki links can't be turned into proper links until all the articles have been parsed, since the
+// target might not have been yet, so they're rendered as placeholder comments at first, and filled
+// in by `resolve_wiki_links` once everything has been parsed. The target and text are
+// percent-encoded in the placeholder, so nothing in them can end the comment early.
+const PLACEHOLDER_PREFIX: &str = "<!--wikilink:";
+
+lazy_static! {
+    // [[target]] or [[target|link text]]
+    static ref WIKI_LINK: Regex = Regex::new(r"\[\[([^\[\]|\n]+)(?:\|([^\[\]\n]+))?\]\]").unwrap();
+    static ref PLACEHOLDER: Regex =
+        Regex::new(r"<!--wikilink:([%0-9A-Za-z]*)\|([%0-9A-Za-z]*)-->").unwrap();
+}
+
+/// A wiki link whose target couldn't be found.
+#[derive(Serialize, Clone, Debug)]
+pub struct UnresolvedLink {
+    pub source_title: String,
+    pub source_slug: String,
+    pub target: String,
+}
+
+fn escaped(s: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, s).ok();
+    escaped
+}
+
+fn encoded(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+fn decoded(s: &str) -> String {
+    percent_decode_str(s).decode_utf8_lossy().into_owned()
+}
+
+/// The text a wiki link placeholder stands for, which is the link text if it has any, or else
+/// its target, for when only plain text is needed.
+pub fn wiki_link_text(html: &str) -> Option<String> {
+    let html = html.trim();
+    let caps = PLACEHOLDER.captures(html)?;
+    if caps.get(0)?.as_str() != html {
+        return None;
+    }
+    let label = decoded(&caps[2]);
+    Some(if label.is_empty() {
+        decoded(&caps[1])
+    } else {
+        label
+    })
+}
+
+// Splits text into plain text and placeholders for any wiki links in it
+fn split_wiki_links(text: &str, output: &mut Vec<Event>) {
+    let mut last = 0;
+    for caps in WIKI_LINK.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        if whole.start() > last {
+            output.push(Event::Text(text[last..whole.start()].to_string().into()));
+        }
+        let target = caps[1].trim();
+        let label = caps.get(2).map(|m| m.as_str().trim()).unwrap_or("");
+        output.push(Event::Html(
+            format!(
+                "{PLACEHOLDER_PREFIX}{}|{}-->",
+                encoded(target),
+                encoded(label)
+            )
+            .into(),
+        ));
+        last = whole.end();
+    }
+    if last < text.len() {
+        output.push(Event::Text(text[last..].to_string().into()));
+    }
+}
+
+/// Replaces `[[target]]` and `[[target|text]]` in text, other than in code blocks, with
+/// placeholders to be resolved later. The parser splits text up at square brackets, so adjacent
+/// text events are joined up first.
+pub fn wiki_link_events<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut output: Vec<Event> = Vec::new();
+    let mut text = String::new();
+    let mut in_code_block = false;
+
+    for event in events {
+        match event {
+            Event::Text(t) if !in_code_block => {
+                text.push_str(&t);
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            _ => {}
+        }
+        if !text.is_empty() {
+            split_wiki_links(&text, &mut output);
+            text.clear();
+        }
+        output.push(event);
+    }
+    if !text.is_empty() {
+        split_wiki_links(&text, &mut output);
+    }
+
+    output
+}
+
+/// The same as `wiki_link_events`, but with each wiki link replaced by its text, for working
+/// with the plain text of an article.
+pub fn wiki_link_text_events<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    // Each link's text is joined to the text around it, as it was before it was split out
+    let mut output: Vec<Event> = Vec::new();
+    for event in wiki_link_events(events) {
+        let text = match &event {
+            Event::Text(text) => text.to_string(),
+            Event::Html(html) => match wiki_link_text(html) {
+                Some(text) => text,
+                None => {
+                    output.push(event);
+                    continue;
+                }
+            },
+            _ => {
+                output.push(event);
+                continue;
+            }
+        };
+        match output.last_mut() {
+            Some(Event::Text(previous)) => *previous = format!("{previous}{text}").into(),
+            _ => output.push(Event::Text(text.into())),
+        }
+    }
+    output
+}
+
+/// The articles wiki links can point at, looked up by slug or by title.
+pub struct LinkTargets {
+    titles_by_slug: HashMap<String, String>,
+    slugs_by_title: HashMap<String, String>,
+}
+
+impl LinkTargets {
+    // Only published articles can be linked to, as anything else isn't found by its link.
+    // Scheduled ones are linked to once they're published and everything's linked up again.
+    pub fn new(articles: &[ParsedArticle], now: i64) -> Self {
+        let published = || articles.iter().filter(move |a| a.is_published(now));
+        Self {
+            titles_by_slug: published()
+                .map(|a| (a.slug.clone(), a.title.clone()))
+                .collect(),
+            slugs_by_title: published()
+                .map(|a| (a.title.to_lowercase(), a.slug.clone()))
+                .collect(),
+        }
+    }
+
+    // Finds the slug and title of the article a wiki link points at. Targets can be a slug, a
+    // title, or anything that gives the same slug as one.
+    fn find(&self, target: &str) -> Option<(&str, &str)> {
+        let slug: String = Slug::new(target).into();
+        let slug = if self.titles_by_slug.contains_key(target) {
+            target
+        } else if let Some(slug) = self.slugs_by_title.get(&target.to_lowercase()) {
+            slug
+        } else {
+            &slug
+        };
+        self.titles_by_slug
+            .get_key_value(slug)
+            .map(|(slug, title)| (slug.as_str(), title.as_str()))
+    }
+}
+
+// Fills in wiki link placeholders in `html`, returning the slugs of the articles linked to and
+// the targets that couldn't be found.
+fn resolve_in(html: &str, targets: &LinkTargets) -> (String, Vec<String>, Vec<String>) {
+    let mut linked = Vec::new();
+    let mut unresolved = Vec::new();
+    let resolved = PLACEHOLDER.replace_all(html, |caps: &Captures| {
+        let target = decoded(&caps[1]);
+        let label = decoded(&caps[2]);
+        match targets.find(&target) {
+            Some((slug, title)) => {
+                linked.push(slug.to_string());
+                let text = if label.is_empty() { title } else { &label };
+                format!("<a href=\"/article/{slug}\">{}</a>", escaped(text))
+            }
+            None => {
+                let text = escaped(if label.is_empty() { &target } else { &label });
+                unresolved.push(target);
+                format!("<span class=\"unresolved-link\">{text}</span>")
+            }
+        }
+    });
+    (resolved.into_owned(), linked, unresolved)
+}
+
+/// Turns wiki link placeholders in the given articles into links to the articles in `targets`,
+/// recording which articles each one links to. Returns any links whose target couldn't be found.
+pub fn resolve_wiki_links(
+    articles: &mut [ParsedArticle],
+    targets: &LinkTargets,
+) -> Vec<UnresolvedLink> {
+    let mut unresolved_links = Vec::new();
+
+    for article in articles.iter_mut() {
+        if !article.parsed_content.contains(PLACEHOLDER_PREFIX) {
+            continue;
+        }
+        let (content, linked, unresolved) = resolve_in(&article.parsed_content, targets);
+        article.parsed_content = content;
+        if let Some(preview_html) = &article.preview_html {
+            article.preview_html = Some(resolve_in(preview_html, targets).0);
+        }
+
+        article.links_to = linked;
+        article.links_to.sort();
+        article.links_to.dedup();
+        for target in unresolved {
+            log::warn!("Unresolved wiki link to '{}' in {}", target, article.slug);
+            unresolved_links.push(UnresolvedLink {
+                source_title: article.title.clone(),
+                source_slug: article.slug.clone(),
+                target,
+            });
+        }
+    }
+
+    unresolved_links
+}
+
+/// Lists, on each article, the published articles that link to it.
+pub fn set_backlinks(articles: &mut [ParsedArticle], now: i64) {
+    let mut backlinks: HashMap<String, Vec<ArticlePrevNext>> = HashMap::new();
+    for source in articles.iter().filter(|a| a.is_published(now)) {
+        for target in &source.links_to {
+            if *target != source.slug {
+                backlinks
+                    .entry(target.clone())
+                    .or_default()
+                    .push(ArticlePrevNext::from(source));
+            }
+        }
+    }
+    for article in articles.iter_mut() {
+        article.backlinks = backlinks.remove(&article.slug).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{html, Parser};
+
+    fn render(markdown: &str, targets: &LinkTargets) -> String {
+        let mut output = String::new();
+        html::push_html(
+            &mut output,
+            wiki_link_events(Parser::new(markdown)).into_iter(),
+        );
+        resolve_in(&output, targets).0
+    }
+
+    #[test]
+    fn labels_are_encoded() {
+        let targets = LinkTargets {
+            titles_by_slug: [("other".to_string(), "Other & more".to_string())].into(),
+            slugs_by_title: HashMap::new(),
+        };
+        assert_eq!(
+            render("[[other|a --> b & c]] [[other]] [[missing]]", &targets),
+            "<p><a href=\"/article/other\">a --&gt; b &amp; c</a> \
+             <a href=\"/article/other\">Other &amp; more</a> \
+             <span class=\"unresolved-link\">missing</span></p>\n"
+        );
+    }
+}
@@ -119,13 +119,13 @@ impl Comments {
         let cl = CommentLine::from_comment(comment, slug);
         let file = OpenOptions::new().append(true).open(&self.filename);
 
-        if file.is_err() {
-            log::error!("Failed to open comments file for appending");
-        } else {
+        if let Ok(mut file) = file {
             let line = json!(cl);
-            if let Err(e) = writeln!(file.unwrap(), "{}", &line) {
+            if let Err(e) = writeln!(file, "{}", &line) {
                 log::error!("Failed to save comment:\n{}\nError: {}", &line, e);
             }
+        } else {
+            log::error!("Failed to open comments file for appending");
         }
     }
 
@@ -157,7 +157,7 @@ impl Comments {
             return Err("IP is rate limited".into());
         }
 
-        if let Some(article_comments) = self.comments.get_mut(&slug.to_string()) {
+        if let Some(article_comments) = self.comments.get_mut(slug) {
             article_comments.push(comment.clone());
         } else {
             self.comments
@@ -1,43 +1,181 @@
+use crate::article::aliases::SlugAliases;
 use crate::article::builder::ParsedArticle;
+use crate::article::cache::ArticleCache;
 use crate::article::gather_fs_articles;
+use crate::article::link_check::{check_links, BrokenLinks};
+use crate::article::related::set_related_articles;
+use crate::article::search::SearchIndex;
+use crate::article::storage::{gather_fs_pages, next_scheduled};
+use crate::article::wiki_links::{resolve_wiki_links, set_backlinks, LinkTargets, UnresolvedLink};
 use crate::comments::Comments;
 use crate::config::Config;
 use crate::errors::ParseError;
+use crate::handlers::create_timestamp;
+use crate::handlers::search::{API_RATE_LIMIT, API_RATE_WINDOW};
 use crate::hb::create_handlebars;
+use crate::hb::helpers::{PageLinks, RenderTags};
+use crate::rate_limit::RateLimiter;
+use crate::redirects::Redirects;
+use crate::tags::TagMetadata;
+use crate::SharedData;
 use handlebars::Handlebars;
+use parking_lot::Mutex;
 use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
 
 pub struct CommonData {
     pub hbs: Handlebars<'static>,
     pub articles: Vec<ParsedArticle>,
+    // Behind its own lock, so that rebuilds can use it without holding the lock on everything else
+    pub article_cache: Arc<Mutex<ArticleCache>>,
+    pub pages: Vec<ParsedArticle>,
+    pub unresolved_links: Vec<UnresolvedLink>,
+    pub broken_links: Vec<BrokenLinks>,
+    pub search_index: SearchIndex,
+    // Behind its own lock, so that API requests only need to read the rest of the data
+    pub search_rate_limiter: Mutex<RateLimiter>,
     pub comments: Comments,
+    pub slug_aliases: SlugAliases,
+    pub redirects: Redirects,
+    pub tag_metadata: TagMetadata,
     pub config: Config,
     pub session_id: Option<String>,
     pub thumb_progress: HashSet<PathBuf>,
     pub initial_remaining_thumbs: usize,
+    pub next_scheduled: Option<i64>,
+    // Tells any pages open in live reload mode that something has changed
+    pub reload_tx: broadcast::Sender<()>,
+    // Numbers each rebuild as it starts, and which one the current articles came from, so one
+    // that finishes after a later one doesn't replace its results with older ones
+    rebuilds_started: AtomicU64,
+    rebuild_applied: u64,
+}
+
+// Everything that's worked out from the content directory on a rebuild. It's built without
+// holding the lock on the shared data, so requests are still served from the old articles in the
+// meantime, and then swapped in all at once.
+struct ContentIndex {
+    articles: Vec<ParsedArticle>,
+    pages: Vec<ParsedArticle>,
+    unresolved_links: Vec<UnresolvedLink>,
+    broken_links: Vec<BrokenLinks>,
+    search_index: SearchIndex,
+    slug_aliases: SlugAliases,
+    redirects: Redirects,
+    tag_metadata: TagMetadata,
+    next_scheduled: Option<i64>,
+}
+
+impl ContentIndex {
+    fn build(config: &Config, article_cache: &Mutex<ArticleCache>) -> Result<Self, ParseError> {
+        let slug_aliases = SlugAliases::load(config);
+        let redirects = Redirects::load(config);
+        let tag_metadata = TagMetadata::load(config);
+        let mut pages = gather_fs_pages(config);
+        let mut articles = {
+            let mut cache = article_cache.lock();
+            cache.use_tag_aliases(tag_metadata.aliases());
+            gather_fs_articles(config, tag_metadata.aliases(), &mut cache)?
+        };
+        let now = create_timestamp();
+
+        // Wiki links in articles and pages, which can only link to articles, are resolved once
+        // everything has been parsed. Articles are cached before this, so cached ones still need
+        // it.
+        let targets = LinkTargets::new(&articles, now);
+        let mut unresolved_links = resolve_wiki_links(&mut articles, &targets);
+        unresolved_links.extend(resolve_wiki_links(&mut pages, &targets));
+        set_backlinks(&mut articles, now);
+
+        let broken_links = check_links(
+            &articles,
+            &pages,
+            &tag_metadata,
+            &slug_aliases,
+            &redirects,
+            &config.content_dir,
+            now,
+        );
+        set_related_articles(&mut articles, config.related_articles, now);
+
+        Ok(Self {
+            next_scheduled: next_scheduled(&articles, now),
+            search_index: SearchIndex::new(&articles),
+            articles,
+            pages,
+            unresolved_links,
+            broken_links,
+            slug_aliases,
+            redirects,
+            tag_metadata,
+        })
+    }
 }
 
 impl CommonData {
     pub fn new() -> Self {
         let config = Config::load().expect("Failed to load config");
-        let articles = gather_fs_articles(&config).expect("gather FS articles");
-        let comments = Comments::load(&config);
-        Self {
+        let article_cache = Arc::new(Mutex::new(ArticleCache::default()));
+        let index = ContentIndex::build(&config, &article_cache).expect("gather FS articles");
+        let mut data = Self {
             hbs: create_handlebars(&config),
-            articles,
-            comments,
+            articles: index.articles,
+            article_cache,
+            pages: index.pages,
+            unresolved_links: index.unresolved_links,
+            broken_links: index.broken_links,
+            search_index: index.search_index,
+            search_rate_limiter: Mutex::new(RateLimiter::new(API_RATE_LIMIT, API_RATE_WINDOW)),
+            comments: Comments::load(&config),
+            slug_aliases: index.slug_aliases,
+            redirects: index.redirects,
+            tag_metadata: index.tag_metadata,
             config,
             session_id: None,
             thumb_progress: HashSet::new(),
             initial_remaining_thumbs: 0,
-        }
+            next_scheduled: index.next_scheduled,
+            reload_tx: broadcast::channel(16).0,
+            rebuilds_started: AtomicU64::new(0),
+            rebuild_applied: 0,
+        };
+        data.register_content_helpers();
+        data
     }
 
-    pub fn rebuild(&mut self) -> Result<(), ParseError> {
-        gather_fs_articles(&self.config).map(|articles| {
-            self.articles = articles;
-        })
+    fn apply(&mut self, index: ContentIndex) {
+        self.articles = index.articles;
+        self.pages = index.pages;
+        self.unresolved_links = index.unresolved_links;
+        self.broken_links = index.broken_links;
+        self.search_index = index.search_index;
+        self.slug_aliases = index.slug_aliases;
+        self.redirects = index.redirects;
+        self.tag_metadata = index.tag_metadata;
+        self.next_scheduled = index.next_scheduled;
+        self.register_content_helpers();
+    }
+
+    /// Updates the `page_links` and `render_tags` helpers with the current pages and tag names.
+    /// This needs doing whenever those or the handlebars registry change.
+    pub fn register_content_helpers(&mut self) {
+        self.hbs
+            .register_helper("page_links", Box::new(PageLinks::new(&self.pages)));
+        self.hbs.register_helper(
+            "render_tags",
+            Box::new(RenderTags::new(self.tag_metadata.display_names())),
+        );
+    }
+
+    /// Whether a scheduled article's publication time has passed since the articles were last
+    /// linked together.
+    pub fn has_due_articles(&self) -> bool {
+        self.next_scheduled
+            .map(|t| t <= create_timestamp())
+            .unwrap_or(false)
     }
 }
 
@@ -46,3 +184,27 @@ impl Default for CommonData {
         Self::new()
     }
 }
+
+/// Rebuilds the articles and pages, and everything worked out from them, from the content
+/// directory. The write lock is only taken to swap the results in at the end, so the caller
+/// mustn't be holding either lock.
+pub fn rebuild(data: &SharedData) -> Result<(), ParseError> {
+    let (rebuild, config, article_cache) = {
+        let data = data.read();
+        (
+            data.rebuilds_started.fetch_add(1, Ordering::SeqCst) + 1,
+            data.config.clone(),
+            data.article_cache.clone(),
+        )
+    };
+    let index = ContentIndex::build(&config, &article_cache)?;
+
+    let mut data = data.write();
+    if rebuild > data.rebuild_applied {
+        data.apply(index);
+        data.rebuild_applied = rebuild;
+    } else {
+        log::debug!("Discarding rebuild {rebuild}, as a later one has already finished");
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::redirects::RedirectRule;
 use serde::{Deserialize, Serialize};
 use std::fs;
 
@@ -18,18 +19,105 @@ pub struct Config {
     pub blog_description: String,
     pub blog_host: String,
     pub max_preview_length: usize,
+    #[serde(default = "default_words_per_minute")]
+    pub words_per_minute: usize,
+    #[serde(default = "default_related_articles")]
+    pub related_articles: usize,
     pub info_html: String,
+    #[serde(default)]
+    pub directory_tags: bool,
+    #[serde(default)]
+    pub watch_content: bool,
+    #[serde(default)]
+    pub dev_live_reload: bool,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    #[serde(default = "default_redirects")]
+    pub redirects: Vec<RedirectRule>,
 
     #[serde(skip)]
     pub secrets: Secrets,
 }
 
+fn default_words_per_minute() -> usize {
+    200
+}
+
+fn default_related_articles() -> usize {
+    5
+}
+
+// Paths used by older versions of Velum, which used to be redirected by the router itself, so
+// existing config files without any redirects keep them.
+fn default_redirects() -> Vec<RedirectRule> {
+    vec![
+        RedirectRule {
+            from: String::from("/articles/:slug"),
+            to: String::from("/article/:slug"),
+            status: 301,
+        },
+        RedirectRule {
+            from: String::from("/tag/:tag/"),
+            to: String::from("/tag/:tag"),
+            status: 301,
+        },
+    ]
+}
+
+// Options for how articles' Markdown is rendered to HTML
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    // Articles with at least this many headings get a table of contents, unless their front
+    // matter says otherwise. Zero means only when the front matter asks for one.
+    pub toc_min_headings: usize,
+    pub heading_permalinks: bool,
+    pub tables: bool,
+    pub footnotes: bool,
+    pub strikethrough: bool,
+    pub task_lists: bool,
+    // Highlight fenced code blocks when rendering, rather than leaving it to Prism in the browser.
+    // Blocks in languages that aren't recognised are still left for Prism.
+    pub server_highlighting: bool,
+}
+
+impl MarkdownConfig {
+    pub fn parser_options(&self) -> pulldown_cmark::Options {
+        use pulldown_cmark::Options;
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.task_lists);
+        options
+    }
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            toc_min_headings: 4,
+            heading_permalinks: true,
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            task_lists: true,
+            server_highlighting: false,
+        }
+    }
+}
+
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Secrets {
     pub admin_password_hash: Option<String>,
 }
 
 impl Config {
+    // Debug builds always have live reload, release builds only if it's switched on
+    pub fn live_reload_enabled(&self) -> bool {
+        cfg!(debug_assertions) || self.dev_live_reload
+    }
+
     pub fn load() -> Result<Self, std::io::Error> {
         let s = fs::read_to_string(CONFIG_FILE)?;
         let mut config: Self = toml::from_str(&s)?;
@@ -2,6 +2,10 @@ pub mod admin;
 pub mod article;
 pub mod comment;
 pub mod index;
+pub mod live_reload;
+pub mod page;
+pub mod search;
+pub mod series;
 pub mod static_files;
 
 use std::{
@@ -11,11 +15,14 @@ use std::{
 };
 
 use axum::{
-    http::{StatusCode, Uri},
-    response::Html,
+    extract::State,
+    http::{header, StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
 };
 use tower_cookies::Cookies;
 
+use crate::{CommonData, SharedData};
+
 pub type HtmlResponse = (StatusCode, Html<String>);
 
 pub fn create_timestamp() -> i64 {
@@ -65,8 +72,8 @@ pub fn render_error_page<T: std::fmt::Display>(
             PathBuf::from(c.content_dir).join("errors")
         };
     }
-    let message = if additional_text.is_some() {
-        format!("HTTP error {:?}: {}", status, additional_text.unwrap())
+    let message = if let Some(text) = additional_text {
+        format!("HTTP error {status:?}: {text}")
     } else {
         format!("HTTP error {status:?}")
     };
@@ -97,6 +104,11 @@ pub fn server_error_page(msg: &str) -> HtmlResponse {
     )
 }
 
+// axum's own Redirect only offers 303, 307 and 308 responses
+pub fn redirect_with_status(status: StatusCode, location: &str) -> Response {
+    (status, [(header::LOCATION, location.to_string())]).into_response()
+}
+
 pub fn not_found(uri: Option<Uri>) -> HtmlResponse {
     (
         StatusCode::NOT_FOUND,
@@ -104,6 +116,23 @@ pub fn not_found(uri: Option<Uri>) -> HtmlResponse {
     )
 }
 
-pub async fn not_found_handler(uri: Option<Uri>) -> HtmlResponse {
-    not_found(uri)
+// Checks the configured redirects for the requested path, before giving up with a 404.
+pub fn redirect_or_not_found(data: &CommonData, uri: Uri) -> Response {
+    match data.redirects.find(uri.path()) {
+        Some((410, _)) => (
+            StatusCode::GONE,
+            Html(render_error_page(StatusCode::GONE, Some(uri))),
+        )
+            .into_response(),
+        Some((status, to)) => {
+            log::info!("Redirecting {} to {} ({})", uri.path(), to, status);
+            // Statuses are checked to be valid when the redirects are loaded
+            redirect_with_status(StatusCode::from_u16(status).unwrap(), &to)
+        }
+        None => not_found(Some(uri)).into_response(),
+    }
+}
+
+pub async fn not_found_handler(State(data): State<SharedData>, uri: Uri) -> Response {
+    redirect_or_not_found(&data.read(), uri)
 }
@@ -1,7 +1,7 @@
 mod thumbnails;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     error::Error,
     fs::{self, remove_file, OpenOptions},
     io::{Error as IoError, Write},
@@ -23,8 +23,16 @@ use chrono::prelude::*;
 use tower_cookies::Cookies;
 
 use super::{empty_response, server_error, server_error_page};
-use crate::{article::storage, commondata::CommonData, SharedData};
-use thumbnails::{get_image_list, ImageListEntry, NameParts, ThumbsRemaining};
+use crate::{
+    article::{
+        storage,
+        tag_rename::{rename_tags, TagRenameReport},
+    },
+    commondata::{rebuild, CommonData},
+    slug::Slug,
+    SharedData,
+};
+use thumbnails::{get_image_list, get_unused_images, ImageListEntry, NameParts, ThumbsRemaining};
 
 const THIRTY_DAYS: i64 = 60 * 60 * 24 * 30;
 const SEE_OTHER: u16 = 303;
@@ -37,6 +45,13 @@ pub struct LoginFormData {
     password: String,
 }
 
+#[derive(Deserialize)]
+pub struct RenameTagsFormData {
+    // Comma-separated
+    from: String,
+    to: String,
+}
+
 struct UploadedImageData {
     file_name: String,
     bytes: Result<Bytes, MultipartError>,
@@ -60,7 +75,7 @@ macro_rules! ensure_authorized {
     };
 }
 
-fn needs_to_log_in(data: &SharedData, cookies: &Cookies) -> bool {
+pub fn needs_to_log_in(data: &SharedData, cookies: &Cookies) -> bool {
     let data = data.read();
     let session_id = cookies
         .get("velum_session_id")
@@ -154,9 +169,7 @@ pub async fn rebuild_index_handler(
 ) -> HtmlOrRedirect {
     ensure_logged_in!(data, cookies);
 
-    let mut data = data.write();
-
-    if let Err(e) = data.rebuild() {
+    if let Err(e) = rebuild(&data) {
         log::error!("Failed to rebuild article index index: {e:?}");
         Ok(server_error_page(&format!(
             "Failed to render article in index. Error: {e:?}"
@@ -172,15 +185,15 @@ pub async fn create_article_handler(
     content: String,
 ) -> HtmlOrStatus {
     ensure_authorized!(data, cookies);
-    let mut data = data.write();
-    match storage::create_article(&content, &mut data) {
+    let created = storage::create_article(&content, &mut data.write());
+    match created {
         Ok(view) => {
             log::info!("Created article '{}' on disk.", view.slug);
-            if let Err(err) = data.rebuild() {
+            if let Err(err) = rebuild(&data) {
                 log::error!("Failed to rebuild article index: {:?}", err);
                 Ok(server_error("Error rebuilding article index"))
             } else {
-                match data.hbs.render("_admin_article_list_item", &view) {
+                match data.read().hbs.render("_admin_article_list_item", &view) {
                     Ok(b) => Ok((StatusCode::OK, Html(b))),
                     Err(e) => {
                         log::error!("Failed to render list item: {:?}", e);
@@ -203,13 +216,13 @@ pub async fn update_article_handler(
     new_content: String,
 ) -> HtmlOrStatus {
     ensure_authorized!(data, cookies);
-    let mut data = data.write();
-    if let Err(err) = storage::update_article(&slug, &new_content, &mut data) {
+    let updated = storage::update_article(&slug, &new_content, true, &mut data.write());
+    if let Err(err) = updated {
         log::error!("Failed to update article: {:?}", err);
         Ok(server_error("Error upating article"))
     } else {
         log::info!("Updated article '{}' on disk.", &slug);
-        if let Err(err) = data.rebuild() {
+        if let Err(err) = rebuild(&data) {
             log::error!("Failed to rebuild article index: {:?}", err);
             Ok(server_error("Error rebuilding article index"))
         } else {
@@ -234,8 +247,7 @@ pub async fn delete_article_handler(
         Ok(server_error("Error deleting article"))
     } else {
         log::info!("Deleted article '{}' from disk.", &slug);
-        let mut wdata = data.write();
-        if let Err(err) = wdata.rebuild() {
+        if let Err(err) = rebuild(&data) {
             log::error!("Failed to rebuild article index: {:?}", err);
             Ok(server_error("Error rebuilding article index"))
         } else {
@@ -244,31 +256,25 @@ pub async fn delete_article_handler(
     }
 }
 
-pub async fn delete_image_handler(
-    Path(path): Path<String>,
-    State(data): State<SharedData>,
-    cookies: Cookies,
-) -> HtmlOrStatus {
-    ensure_authorized!(data, cookies);
-    match NameParts::new(&path) {
+// Deletes an image and its thumbnail, returning whether both were deleted. Any errors are logged.
+fn delete_image_files<P: AsRef<OsPath>>(path: P) -> bool {
+    let path = path.as_ref();
+    match NameParts::new(path) {
         Ok(parts) => match ImageListEntry::thumbnail_file_name(&parts.file_name) {
             Ok(thumb_name) => {
                 let thumb_path = parts.dir.join(&thumb_name);
-                let (ri, rt) = (remove_file(&path), remove_file(thumb_path));
-                if ri.is_err() {
-                    log::error!("Failed to delete image {:?}: {:?}", path, ri.unwrap_err());
-                    return Ok(server_error("Error deleting image"));
+                let (ri, rt) = (remove_file(path), remove_file(thumb_path));
+                if let Err(e) = ri {
+                    log::error!("Failed to delete image {:?}: {:?}", path, e);
+                    return false;
                 }
                 log::info!("Deleted image {:?}", path);
-                if rt.is_err() {
-                    log::error!(
-                        "Failed to delete thumbnail {:?}: {:?}",
-                        path,
-                        rt.unwrap_err()
-                    );
-                    return Ok(server_error("Error deleting image"));
+                if let Err(e) = rt {
+                    log::error!("Failed to delete thumbnail {:?}: {:?}", path, e);
+                    return false;
                 }
                 log::info!("Deleted thumbnail {:?}", thumb_name);
+                true
             }
             Err(e) => {
                 log::error!(
@@ -276,18 +282,95 @@ pub async fn delete_image_handler(
                     parts.file_name,
                     e
                 );
-                return Ok(server_error("Error deleting image"));
+                false
             }
         },
         Err(e) => {
             log::error!("Failed to extract parts from {:?}: {:?}", path, e);
-            return Ok(server_error("Error deleting image"));
+            false
         }
     }
+}
+
+pub async fn delete_image_handler(
+    Path(path): Path<String>,
+    State(data): State<SharedData>,
+    cookies: Cookies,
+) -> HtmlOrStatus {
+    ensure_authorized!(data, cookies);
+    if !delete_image_files(&path) {
+        return Ok(server_error("Error deleting image"));
+    }
+
+    image_list_handler(State(data), cookies).await
+}
+
+// Images are only checked against the articles and pages as they are now, so anything referring
+// to an image from outside them will lose it.
+pub async fn delete_unused_images_handler(
+    State(data): State<SharedData>,
+    cookies: Cookies,
+) -> HtmlOrStatus {
+    ensure_authorized!(data, cookies);
+    let unused = get_unused_images(&data);
+    let failed = unused
+        .iter()
+        .filter(|path| !delete_image_files(path))
+        .count();
+    log::info!("Deleted {} unused images", unused.len() - failed);
+    if failed > 0 {
+        return Ok(server_error(&format!(
+            "Failed to delete {failed} of {} unused images",
+            unused.len()
+        )));
+    }
 
     image_list_handler(State(data), cookies).await
 }
 
+// Renames or merges tags in every article's source. Tags are written as slugs, so that nothing in
+// the new name can break the list they're written in.
+pub async fn rename_tags_handler(
+    State(data): State<SharedData>,
+    cookies: Cookies,
+    Form(form_data): Form<RenameTagsFormData>,
+) -> Result<Json<TagRenameReport>, StatusCode> {
+    ensure_authorized!(data, cookies);
+
+    let to: String = Slug::new(&form_data.to).into();
+    let from: HashSet<String> = {
+        let data = data.read();
+        let aliases = data.tag_metadata.aliases();
+        form_data
+            .from
+            .split(',')
+            .map(|t| aliases.canonical(&Slug::new(t).to_string()).to_string())
+            .filter(|t| !t.is_empty() && *t != to)
+            .collect()
+    };
+    if to.is_empty() || from.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match rename_tags(&from, &to, &data) {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => {
+            log::error!("Failed to rebuild article index after renaming tags: {e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// How many articles have each tag, including drafts and scheduled articles, not counting parent
+// tags
+fn tag_counts(data: &CommonData) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for tag in data.articles.iter().flat_map(|a| a.tags.iter()) {
+        *counts.entry(tag.as_str()).or_insert(0) += 1;
+    }
+    counts
+}
+
 fn get_thumbs_remaining(data: &CommonData) -> ThumbsRemaining {
     let count = data.thumb_progress.len();
     let total = data.initial_remaining_thumbs;
@@ -331,6 +414,7 @@ fn save_file<P: AsRef<OsPath>>(file_name: P, bytes: &Bytes) -> Result<(), IoErro
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(&file_name)?;
     log::info!("Saving file {:?}", file_name.as_ref());
     file.write_all(bytes)
@@ -386,6 +470,9 @@ pub async fn admin_page_handler(
             "title": "Blog Admin",
             "blog_title": blog_title,
             "articles": &data.articles,
+            "unresolved_links": &data.unresolved_links,
+            "broken_links": &data.broken_links,
+            "tag_counts": tag_counts(&data),
             "content_dir": &data.config.content_dir,
         }),
     ) {
@@ -414,11 +501,14 @@ pub async fn image_list_handler(State(data): State<SharedData>, cookies: Cookies
     let data = data.read();
     let dir_keys = sorted_dir_keys(&image_dirs);
 
+    let unused_count: usize = image_dirs.values().map(|d| d.unused_count).sum();
+
     match data.hbs.render(
         "_admin_image_list",
         &json!({
             "dir_keys": dir_keys,
             "image_dirs": image_dirs,
+            "unused_count": unused_count,
             "thumbs_remaining": thumbs_remaining,
         }),
     ) {
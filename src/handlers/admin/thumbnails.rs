@@ -2,7 +2,7 @@ use std::{
     collections::HashMap,
     error::Error,
     ffi::OsString,
-    fmt,
+    fmt, fs,
     path::{Path as OsPath, PathBuf},
 };
 
@@ -11,9 +11,11 @@ use image::{
     imageops::{resize, FilterType},
     GenericImageView, ImageFormat,
 };
+use percent_encoding::percent_decode_str;
 use serde::Serialize;
 use walkdir::{DirEntry, WalkDir};
 
+use crate::io::paths_with_ext_in_tree;
 use crate::SharedData;
 
 // WARNING: if this is changed, ALL thumbnails will be regenerated. To avoid this, rename your
@@ -112,10 +114,11 @@ impl std::convert::From<image::ImageError> for ThumbError {
 pub struct ImageListEntry {
     pub thumbnail_file_name: String,
     pub orig_file_name: String,
+    pub unused: bool,
 }
 
 impl ImageListEntry {
-    fn new<P: AsRef<OsPath>>(file_name: P) -> Result<Self, ThumbError> {
+    fn new<P: AsRef<OsPath>>(file_name: P, unused: bool) -> Result<Self, ThumbError> {
         let file_name = file_name.as_ref();
         let thumbnail_file_name = Self::thumbnail_file_name(file_name)?
             .to_string_lossy()
@@ -124,6 +127,7 @@ impl ImageListEntry {
         Ok(Self {
             orig_file_name,
             thumbnail_file_name,
+            unused,
         })
     }
     pub fn thumbnail_file_name<P: AsRef<OsPath>>(file_name: P) -> Result<PathBuf, ThumbError> {
@@ -145,6 +149,7 @@ impl ImageListEntry {
 pub struct ImageListDir {
     pub dir: String,
     pub file_names: Vec<ImageListEntry>,
+    pub unused_count: usize,
 }
 
 impl ImageListDir {
@@ -153,17 +158,19 @@ impl ImageListDir {
         // These names are for output to HTML
         s.replace('\\', "/")
     }
-    pub fn new(parts: &NameParts) -> Result<Self, ThumbError> {
-        let entry = ImageListEntry::new(&parts.file_name)?;
+    pub fn new(parts: &NameParts, unused: bool) -> Result<Self, ThumbError> {
+        let entry = ImageListEntry::new(&parts.file_name, unused)?;
         Ok(Self {
             dir: Self::reslash(&parts.dir),
             file_names: vec![entry],
+            unused_count: usize::from(unused),
         })
     }
-    pub fn push<P: AsRef<OsPath>>(&mut self, file_name: P) -> Result<(), ThumbError> {
+    pub fn push<P: AsRef<OsPath>>(&mut self, file_name: P, unused: bool) -> Result<(), ThumbError> {
         let file_name = file_name.as_ref().to_string_lossy().to_string();
-        let entry = ImageListEntry::new(file_name)?;
+        let entry = ImageListEntry::new(file_name, unused)?;
         self.file_names.push(entry);
+        self.unused_count += usize::from(unused);
         Ok(())
     }
 }
@@ -268,14 +275,13 @@ fn file_sorter(a: &DirEntry, b: &DirEntry) -> std::cmp::Ordering {
     std::cmp::Ordering::Equal
 }
 
-pub fn get_image_list(data: &SharedData) -> (HashMap<PathBuf, ImageListDir>, ThumbsRemaining) {
-    let dir = PathBuf::from(&data.read().config.content_dir).join("images");
+// All the images in `dir` and its subdirectories, newest first, not counting thumbnails
+fn find_image_files(dir: &OsPath) -> Vec<NameParts> {
     let iter = WalkDir::new(dir)
         .sort_by(file_sorter)
         .into_iter()
         .filter_entry(is_valid_image_file);
 
-    let mut thumbnail_futures = Vec::new();
     let mut image_files: Vec<NameParts> = Vec::new();
     for entry in iter {
         match entry {
@@ -292,7 +298,119 @@ pub fn get_image_list(data: &SharedData) -> (HashMap<PathBuf, ImageListDir>, Thu
             Err(e) => log::error!("Unable to read dir entry: {:?}", e),
         }
     }
+    image_files
+}
+
+// Extensions of the files in the templates and assets directories that can refer to images, e.g.
+// a background image in a stylesheet
+const SITE_FILE_EXTS: [&str; 3] = ["hbs", "css", "js"];
+
+// The text of the templates, stylesheets and scripts, which can use images too
+fn site_file_sources(content_dir: &OsPath) -> Vec<String> {
+    let mut sources = Vec::new();
+    for dir in ["templates", "assets"] {
+        for ext in SITE_FILE_EXTS {
+            paths_with_ext_in_tree(
+                ext,
+                &content_dir.join(dir),
+                |path| match fs::read_to_string(path) {
+                    Ok(s) => sources.push(s),
+                    Err(e) => log::error!("Failed to read {path:?} to look for images: {e:?}"),
+                },
+            );
+        }
+    }
+    sources
+}
+
+// Characters that can carry on a file or directory name
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+// Whether `source` mentions `path` on its own, rather than as part of a longer path
+fn mentions(source: &str, path: &str) -> bool {
+    source.match_indices(path).any(|(start, _)| {
+        let before = source[..start].chars().next_back();
+        let mut after = source[start + path.len()..].chars();
+        let continues = match after.next() {
+            Some('.' | '/') => after.next().is_some_and(is_name_char),
+            Some(c) => is_name_char(c),
+            None => false,
+        };
+        !before.is_some_and(is_name_char) && !continues
+    })
+}
+
+// Which of the images, given by their paths under the images directory, none of the sources
+// mention, either directly or by their thumbnail
+fn unused_among(sources: &[String], rel_paths: &[PathBuf]) -> Vec<bool> {
+    rel_paths
+        .iter()
+        .map(|rel_path| {
+            let mut needles = vec![format!("images/{}", ImageListDir::reslash(rel_path))];
+            if let Some(thumb) = rel_path
+                .file_name()
+                .and_then(|name| ImageListEntry::thumbnail_file_name(name).ok())
+            {
+                let thumb_path = rel_path.with_file_name(thumb);
+                needles.push(format!("images/{}", ImageListDir::reslash(thumb_path)));
+            }
+            !sources
+                .iter()
+                .any(|s| needles.iter().any(|needle| mentions(s, needle)))
+        })
+        .collect()
+}
+
+// Finds which of the images aren't referred to by any article or page, the home page info, or
+// the templates and assets. Images are inserted into articles by their path under the images
+// directory, so that's what's looked for in the sources, once they're percent-decoded.
+fn find_unused(image_files: &[NameParts], images_dir: &OsPath, data: &SharedData) -> Vec<bool> {
+    let content_dir = PathBuf::from(&data.read().config.content_dir);
+    let site_files = site_file_sources(&content_dir);
+    let data = data.read();
+    let sources: Vec<String> = data
+        .articles
+        .iter()
+        .chain(data.pages.iter())
+        .map(|a| a.base_content.as_str())
+        .chain(std::iter::once(data.config.info_html.as_str()))
+        .chain(site_files.iter().map(String::as_str))
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned())
+        .collect();
+
+    let rel_paths: Vec<PathBuf> = image_files
+        .iter()
+        .map(|parts| {
+            parts
+                .path
+                .strip_prefix(images_dir)
+                .unwrap_or(&parts.path)
+                .to_path_buf()
+        })
+        .collect();
+    unused_among(&sources, &rel_paths)
+}
+
+/// Paths of all the images that no article or page refers to.
+pub fn get_unused_images(data: &SharedData) -> Vec<PathBuf> {
+    let dir = PathBuf::from(&data.read().config.content_dir).join("images");
+    let image_files = find_image_files(&dir);
+    let unused = find_unused(&image_files, &dir, data);
+    image_files
+        .into_iter()
+        .zip(unused)
+        .filter_map(|(parts, unused)| unused.then_some(parts.path))
+        .collect()
+}
 
+pub fn get_image_list(data: &SharedData) -> (HashMap<PathBuf, ImageListDir>, ThumbsRemaining) {
+    let dir = PathBuf::from(&data.read().config.content_dir).join("images");
+    let image_files = find_image_files(&dir);
+    let unused = find_unused(&image_files, &dir, data);
+
+    let mut thumbnail_futures = Vec::new();
     let initial_remaining = data.read().thumb_progress.len();
     let mut existing_thumb_count = 0;
     let mut filenames: HashMap<PathBuf, ImageListDir> = HashMap::new();
@@ -309,11 +427,11 @@ pub fn get_image_list(data: &SharedData) -> (HashMap<PathBuf, ImageListDir>, Thu
 
         let key = &parts.dir;
         if let Some(ild) = filenames.get_mut(key) {
-            if let Err(e) = ild.push(&parts.file_name) {
+            if let Err(e) = ild.push(&parts.file_name, unused[i]) {
                 log::error!("Failed to push file name/thumbnail to dirlist: {:?}", e)
             }
         } else {
-            match ImageListDir::new(parts) {
+            match ImageListDir::new(parts, unused[i]) {
                 Ok(ild) => {
                     filenames.insert(key.clone(), ild);
                 }
@@ -346,3 +464,51 @@ pub fn get_image_list(data: &SharedData) -> (HashMap<PathBuf, ImageListDir>, Thu
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unused(source: &str, paths: &[&str]) -> Vec<bool> {
+        let sources = [percent_decode_str(source).decode_utf8_lossy().into_owned()];
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        unused_among(&sources, &paths)
+    }
+
+    #[test]
+    fn percent_encoded_paths() {
+        assert_eq!(
+            unused("![](/images/2023/my%20photo.jpg)", &["2023/my photo.jpg"]),
+            [false]
+        );
+    }
+
+    #[test]
+    fn thumbnails() {
+        assert_eq!(
+            unused(
+                "[![](/images/a_thumbnail.jpg)](/images/b.jpg)",
+                &["a.jpg", "b.jpg", "c.jpg"]
+            ),
+            [false, false, true]
+        );
+    }
+
+    #[test]
+    fn path_boundaries() {
+        assert_eq!(
+            unused(
+                "![](/images/cat.jpg.bak) ![](/images/dog.jpgx) ![](/myimages/fox.png)",
+                &["cat.jpg", "dog.jpg", "fox.png"]
+            ),
+            [true, true, true]
+        );
+        assert_eq!(
+            unused(
+                "See images/cat.jpg. And url(\"../images/dog.jpg\") too",
+                &["cat.jpg", "dog.jpg"]
+            ),
+            [false, false]
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use std::time;
 
 use axum::{
-    extract::{Path, State},
+    extract::{OriginalUri, Path, State},
     http::{StatusCode, Uri},
-    response::{Html, IntoResponse},
+    response::{Html, IntoResponse, Response},
 };
 use headers::HeaderMap;
 use regex::Regex;
 use tower_cookies::Cookies;
 
-use super::{log_elapsed, not_found, server_error, theme};
+use super::{
+    admin::needs_to_log_in, create_timestamp, log_elapsed, redirect_or_not_found,
+    redirect_with_status, server_error, theme,
+};
 use crate::article::{storage::fetch_by_slug, view::ArticleRenderView};
 use crate::SharedData;
 
@@ -44,22 +47,28 @@ fn return_path(blog_host: &str, uri: Option<String>) -> String {
 pub async fn article_text_handler(
     Path(slug): Path<String>,
     State(data): State<SharedData>,
+    cookies: Cookies,
 ) -> impl IntoResponse {
+    let is_admin = !needs_to_log_in(&data, &cookies);
     let data = data.read();
-    if let Some(article) = fetch_by_slug(&slug, &data.articles) {
-        (StatusCode::OK, article.base_content.clone())
-    } else {
-        (StatusCode::NOT_FOUND, "Article not found".to_string())
+    match fetch_by_slug(&slug, &data.articles) {
+        Some(article) if is_admin || article.is_published(create_timestamp()) => {
+            (StatusCode::OK, article.base_content.clone())
+        }
+        _ => (StatusCode::NOT_FOUND, "Article not found".to_string()),
     }
 }
 
 pub async fn article_handler(
     Path(slug): Path<String>,
     State(data): State<SharedData>,
+    OriginalUri(uri): OriginalUri,
     headers: HeaderMap,
     cookies: Cookies,
-) -> impl IntoResponse {
+) -> Response {
     let now = time::Instant::now();
+    // Checked before taking the read lock below, as it needs its own
+    let is_admin = !needs_to_log_in(&data, &cookies);
     let data = data.read();
 
     let referer = headers
@@ -68,19 +77,30 @@ pub async fn article_handler(
 
     let return_path = return_path(&data.config.blog_host, referer);
 
-    if let Some(article) = fetch_by_slug(&slug, &data.articles) {
+    let timestamp = create_timestamp();
+    let article =
+        fetch_by_slug(&slug, &data.articles).filter(|a| is_admin || a.is_published(timestamp));
+
+    if let Some(article) = article {
         let theme = theme(cookies);
-        let render_data =
-            ArticleRenderView::new(article, &data.articles, &return_path, &theme, &data);
+        let render_data = ArticleRenderView::new(article, &return_path, &theme, &data);
         match data.hbs.render("article", &render_data) {
             Ok(rendered_page) => {
                 let reply = (StatusCode::OK, Html(rendered_page));
                 log_elapsed("ARTICLE", Some(&slug), None, now);
-                reply
+                reply.into_response()
+            }
+            Err(e) => {
+                server_error(&format!("Failed to render article. Error: {e:?}")).into_response()
             }
-            Err(e) => server_error(&format!("Failed to render article. Error: {e:?}")),
         }
+    } else if let Some(new_slug) = data.slug_aliases.resolve(&slug, &data.articles, timestamp) {
+        log::info!("Redirecting old slug {slug} to {new_slug}");
+        redirect_with_status(
+            StatusCode::MOVED_PERMANENTLY,
+            &format!("/article/{new_slug}"),
+        )
     } else {
-        not_found(None)
+        redirect_or_not_found(&data, uri)
     }
 }
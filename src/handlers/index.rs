@@ -1,17 +1,19 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect, Response},
+    extract::{OriginalUri, Path, State},
+    http::{StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
 };
 use axum_macros::debug_handler;
 use std::time;
 use tower_cookies::Cookies;
 
-use super::{log_elapsed, server_error, theme};
+use super::{
+    create_timestamp, log_elapsed, redirect_or_not_found, redirect_with_status, server_error, theme,
+};
 
 use crate::article::{
     storage::{fetch_paginated_articles, PaginatedArticles},
-    view::{IndexRenderView, RssArticleView, RssIndexView},
+    view::{IndexRenderView, RssArticleView, RssIndexView, TagsRenderView},
 };
 use crate::CommonData;
 use crate::SharedData;
@@ -40,26 +42,31 @@ fn render_article_list(
 
 #[debug_handler]
 pub async fn home_handler(State(data): State<SharedData>, cookies: Cookies) -> impl IntoResponse {
-    index_handler(Path(String::from("0")), State(data), cookies).await
+    index_handler(
+        Path(String::from("0")),
+        State(data),
+        OriginalUri(Uri::from_static("/")),
+        cookies,
+    )
+    .await
 }
 
 pub async fn index_handler(
     Path(page_or_slug): Path<String>,
     State(data): State<SharedData>,
+    OriginalUri(uri): OriginalUri,
     cookies: Cookies,
 ) -> impl IntoResponse {
-    // Handle legacy article route, i.e. /articles/:slug
-    // NOTE: eventually this should be removed, once the requests for the old route taper off
-    let parse_result = page_or_slug.parse::<usize>();
-    if parse_result.is_err() {
-        return Err(Redirect::permanent(
-            &(String::from("/article/") + &page_or_slug),
-        ));
-    }
-    let page = parse_result.unwrap();
+    let data = data.read();
+
+    // Anything that isn't a page number might be an old path in the redirects list, e.g. the
+    // legacy /articles/:slug article route.
+    let page = match page_or_slug.parse::<usize>() {
+        Ok(page) => page,
+        Err(_) => return Err(redirect_or_not_found(&data, uri)),
+    };
 
     let now = time::Instant::now();
-    let data = data.read();
 
     let page_size = data.config.page_size;
     let article_list = fetch_paginated_articles(page, page_size, None, &data.articles);
@@ -70,10 +77,12 @@ pub async fn index_handler(
     Ok(response)
 }
 
-fn build_rss_articles(data: &CommonData) -> Vec<RssArticleView> {
-    let end_index = std::cmp::min(10, data.articles.len());
-    data.articles[..end_index]
+fn build_rss_articles(data: &CommonData) -> Vec<RssArticleView<'_>> {
+    let now = create_timestamp();
+    data.articles
         .iter()
+        .filter(|a| a.is_published(now))
+        .take(10)
         .map(|a| RssArticleView::from_parsed_article(a, &data.config.blog_url))
         .collect()
 }
@@ -109,7 +118,7 @@ pub async fn tag_home_handler(
     Path(tag): Path<String>,
     State(data): State<SharedData>,
     cookies: Cookies,
-) -> impl IntoResponse {
+) -> Response {
     tag_handler(Path((tag, 1)), State(data), cookies).await
 }
 
@@ -117,13 +126,26 @@ pub async fn tag_handler(
     Path((tag, page)): Path<(String, usize)>,
     State(data): State<SharedData>,
     cookies: Cookies,
-) -> impl IntoResponse {
+) -> Response {
     let now = time::Instant::now();
-    let tag_copy = tag.clone();
     let data = data.read();
-    let page_size = data.config.page_size;
 
-    let article_result = fetch_paginated_articles(page, page_size, Some(&tag), &data.articles);
+    // Aliases are folded into their tag when articles are built, so nothing is tagged with them
+    let tag_aliases = data.tag_metadata.aliases();
+    if tag_aliases.is_alias(&tag) {
+        let canonical = tag_aliases.canonical(&tag);
+        log::info!("Redirecting tag alias {tag} to {canonical}");
+        let location = if page == 1 {
+            format!("/tag/{canonical}")
+        } else {
+            format!("/tag/{canonical}/{page}")
+        };
+        return redirect_with_status(StatusCode::MOVED_PERMANENTLY, &location);
+    }
+
+    let page_size = data.config.page_size;
+    let tags = data.tag_metadata.with_descendants(&tag);
+    let article_result = fetch_paginated_articles(page, page_size, Some(&tags), &data.articles);
 
     let response = render_article_list(
         article_result,
@@ -133,6 +155,28 @@ pub async fn tag_handler(
         theme(cookies),
         &data,
     );
-    log_elapsed("TAG INDEX", Some(&tag_copy), Some(page), now);
-    response
+    log_elapsed("TAG INDEX", Some(&tag), Some(page), now);
+    response.into_response()
+}
+
+// Lists every tag used by a published article, with how many articles have it
+pub async fn tags_handler(State(data): State<SharedData>, cookies: Cookies) -> Response {
+    let now = time::Instant::now();
+    let data = data.read();
+    let timestamp = create_timestamp();
+    let articles: Vec<_> = data
+        .articles
+        .iter()
+        .filter(|a| a.is_published(timestamp))
+        .collect();
+
+    let theme = theme(cookies);
+    let render_data = TagsRenderView::new(&articles, &theme, &data);
+    match data.hbs.render("tags", &render_data) {
+        Ok(rendered_page) => {
+            log_elapsed("TAGS", None, None, now);
+            (StatusCode::OK, Html(rendered_page)).into_response()
+        }
+        Err(e) => server_error(&format!("Failed to render tag list. Error: {e:?}")).into_response(),
+    }
 }
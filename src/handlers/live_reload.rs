@@ -0,0 +1,45 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::not_found;
+use crate::SharedData;
+
+// Sends a `reload` event to the browser whenever the content watcher sees something change. Only
+// available when live reload is enabled.
+pub async fn live_reload_handler(State(data): State<SharedData>) -> Response {
+    let rx = {
+        let data = data.read();
+        if !data.config.live_reload_enabled() {
+            return not_found(None).into_response();
+        }
+        data.reload_tx.subscribe()
+    };
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(()) => {
+                    return Some((
+                        Ok::<_, Infallible>(Event::default().event("reload").data("changed")),
+                        rx,
+                    ))
+                }
+                // Missed reloads don't matter as long as there's at least one
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
@@ -0,0 +1,40 @@
+use std::time;
+
+use axum::{
+    extract::{OriginalUri, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use tower_cookies::Cookies;
+
+use super::{admin::needs_to_log_in, log_elapsed, redirect_or_not_found, server_error, theme};
+use crate::article::{storage::fetch_by_slug, view::PageRenderView};
+use crate::SharedData;
+
+pub async fn page_handler(
+    Path(slug): Path<String>,
+    State(data): State<SharedData>,
+    OriginalUri(uri): OriginalUri,
+    cookies: Cookies,
+) -> Response {
+    let now = time::Instant::now();
+    // Checked before taking the read lock below, as it needs its own
+    let is_admin = !needs_to_log_in(&data, &cookies);
+    let data = data.read();
+
+    let page = fetch_by_slug(&slug, &data.pages).filter(|p| is_admin || !p.draft);
+
+    if let Some(page) = page {
+        let theme = theme(cookies);
+        let render_data = PageRenderView::new(page, &theme, &data);
+        match data.hbs.render("page", &render_data) {
+            Ok(rendered_page) => {
+                log_elapsed("PAGE", Some(&slug), None, now);
+                (StatusCode::OK, Html(rendered_page)).into_response()
+            }
+            Err(e) => server_error(&format!("Failed to render page. Error: {e:?}")).into_response(),
+        }
+    } else {
+        redirect_or_not_found(&data, uri)
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    net::SocketAddr,
+    time::{self, Duration},
+};
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use super::{create_timestamp, log_elapsed, server_error, theme};
+use crate::article::{
+    search::{SearchHit, SearchQuery},
+    view::{ApiSearchView, SearchRenderView},
+};
+use crate::{CommonData, SharedData};
+
+// Search-as-you-type sends a request every time the query changes, so this allows for a fair bit
+// of typing while still stopping anyone from hammering the server.
+pub const API_RATE_LIMIT: usize = 30;
+pub const API_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+// Results only change when the articles are rebuilt, so can be cached for a little while
+const API_CACHE_CONTROL: &str = "public, max-age=60";
+
+const DEFAULT_API_LIMIT: usize = 10;
+const MAX_API_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+    #[serde(default = "first_page")]
+    page: usize,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct ApiSearchParams {
+    #[serde(default)]
+    q: String,
+    #[serde(default = "default_api_limit")]
+    limit: usize,
+}
+
+fn default_api_limit() -> usize {
+    DEFAULT_API_LIMIT
+}
+
+fn published_hits(query: &str, data: &CommonData) -> Vec<SearchHit> {
+    let now = create_timestamp();
+    let query = SearchQuery::parse(query).resolve_tags(&data.tag_metadata);
+    data.search_index
+        .search(&query, |i| data.articles[i].is_published(now))
+}
+
+pub async fn search_handler(
+    Query(params): Query<SearchParams>,
+    State(data): State<SharedData>,
+    cookies: Cookies,
+) -> Response {
+    let now = time::Instant::now();
+    let data = data.read();
+
+    let query = params.q.trim();
+    let hits = published_hits(query, &data);
+
+    let render_data = SearchRenderView::new(query, &hits, params.page, theme(cookies), &data);
+    match data.hbs.render("search", &render_data) {
+        Ok(rendered_page) => {
+            log_elapsed("SEARCH", Some(query), Some(params.page), now);
+            (StatusCode::OK, Html(rendered_page)).into_response()
+        }
+        Err(e) => {
+            server_error(&format!("Failed to render search results. Error: {e:?}")).into_response()
+        }
+    }
+}
+
+pub async fn api_search_handler(
+    Query(params): Query<ApiSearchParams>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(data): State<SharedData>,
+) -> Response {
+    let data = data.read();
+    if !data.search_rate_limiter.lock().allow(addr.ip()) {
+        log::warn!("Rate limited search API requests from {}", addr.ip());
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, API_RATE_WINDOW.as_secs().to_string())],
+            "Too many search requests",
+        )
+            .into_response();
+    }
+
+    let query = params.q.trim();
+    let hits = published_hits(query, &data);
+    let limit = std::cmp::min(params.limit, MAX_API_LIMIT);
+    (
+        [(header::CACHE_CONTROL, API_CACHE_CONTROL)],
+        Json(ApiSearchView::new(query, &hits, limit, &data)),
+    )
+        .into_response()
+}
@@ -0,0 +1,37 @@
+use std::time;
+
+use axum::{
+    extract::{OriginalUri, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use tower_cookies::Cookies;
+
+use super::{create_timestamp, log_elapsed, redirect_or_not_found, server_error, theme};
+use crate::article::{storage::fetch_series, view::SeriesRenderView};
+use crate::SharedData;
+
+pub async fn series_handler(
+    Path(slug): Path<String>,
+    State(data): State<SharedData>,
+    OriginalUri(uri): OriginalUri,
+    cookies: Cookies,
+) -> Response {
+    let now = time::Instant::now();
+    let data = data.read();
+
+    let articles = fetch_series(&slug, &data.articles, create_timestamp());
+    if articles.is_empty() {
+        return redirect_or_not_found(&data, uri);
+    }
+
+    let theme = theme(cookies);
+    let render_data = SeriesRenderView::new(&slug, articles, &theme, &data);
+    match data.hbs.render("series", &render_data) {
+        Ok(rendered_page) => {
+            log_elapsed("SERIES", Some(&slug), None, now);
+            (StatusCode::OK, Html(rendered_page)).into_response()
+        }
+        Err(e) => server_error(&format!("Failed to render series. Error: {e:?}")).into_response(),
+    }
+}
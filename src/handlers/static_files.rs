@@ -116,6 +116,15 @@ fn compile_manifest(
     Ok(last_modified)
 }
 
+/// Recompiles the JS manifest so its modified time, and so its timestamped URL, reflects the
+/// latest of the files it includes.
+pub fn refresh_js_manifest(manifest_path: &PathBuf) {
+    let mut buf = Vec::new();
+    if compile_manifest(manifest_path, &mut buf).is_err() {
+        log::error!("Failed to refresh JS manifest {manifest_path:?}");
+    }
+}
+
 fn build_response(
     filename: &PathBuf,
     last_modified: SystemTime,
@@ -8,34 +8,38 @@ use std::path::{Path, PathBuf};
 
 fn template_name(path: &Path) -> String {
     let stem = path.file_stem().unwrap();
-    stem.to_string_lossy()
-        .to_string()
-        .split('.')
-        .map(|p: &str| p.to_string())
-        .collect::<Vec<String>>()
-        .get(0)
-        .unwrap()
-        .into()
+    stem.to_string_lossy().split('.').next().unwrap().into()
 }
 
 pub fn create_handlebars(config: &Config) -> Handlebars<'static> {
+    try_create_handlebars(config).unwrap_or_else(|e| panic!("{e}"))
+}
+
+// Like `create_handlebars`, but returns an error instead of panicking if any template fails to
+// register, for reloading templates while the server is running.
+pub fn try_create_handlebars(config: &Config) -> Result<Handlebars<'static>, String> {
     let mut hb = Handlebars::new();
 
     let dir = PathBuf::from(&config.content_dir).join("templates");
     if !dir.is_dir() {
-        panic!("Template path {dir:?} is not a directory.");
+        return Err(format!("Template path {dir:?} is not a directory."));
     }
 
     #[cfg(debug_assertions)]
     hb.set_dev_mode(true);
 
+    let mut result = Ok(());
     paths_with_ext_in_dir("hbs", &dir, |path| {
+        if result.is_err() {
+            return;
+        }
         let template_name = template_name(path);
-        hb.register_template_file(&template_name, path)
-            .unwrap_or_else(|e| {
-                panic!("Failed to register template {template_name} with path {path:?}. Error: {e}")
-            })
+        if let Err(e) = hb.register_template_file(&template_name, path) {
+            result = Err(format!(
+                "Failed to register template {template_name} with path {path:?}. Error: {e}"
+            ));
+        }
     });
 
-    register_helpers(hb)
+    result.map(|_| register_helpers(hb))
 }
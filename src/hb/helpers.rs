@@ -1,13 +1,20 @@
 use chrono::prelude::*;
 use chrono::{DateTime, Duration, LocalResult};
-use handlebars::{handlebars_helper, Handlebars};
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperDef, RenderContext, RenderError,
+    ScopedJson,
+};
+use pulldown_cmark::escape::{escape_href, escape_html};
+use serde_json::Value as JsonValue;
 use ordinal::Ordinal;
 use std::{
+    collections::HashMap,
     fs,
     path::{Path, PathBuf},
     time::SystemTime,
 };
 
+use crate::article::builder::ParsedArticle;
 use crate::config::TIMESTAMP_FORMAT;
 
 fn pluralize(word: &str, num: i64) -> (String, i64) {
@@ -84,6 +91,21 @@ handlebars_helper!(age_from_timestamp: |ts: i64| {
     }
 });
 
+handlebars_helper!(in_future: |ts: i64| {
+    ts > Utc::now().timestamp_millis()
+});
+
+// Formats a reading time given in minutes, e.g. "7 min read" or "1 hr 15 min read"
+handlebars_helper!(reading_time: |minutes: u64| {
+    if minutes < 60 {
+        format!("{minutes} min read")
+    } else if minutes % 60 == 0 {
+        format!("{} hr read", minutes / 60)
+    } else {
+        format!("{} hr {} min read", minutes / 60, minutes % 60)
+    }
+});
+
 handlebars_helper!(rfc822_date: |ts: i64| {
     if let LocalResult::Single(dt) = Utc.timestamp_millis_opt(ts) {
         dt.to_rfc2822()
@@ -97,38 +119,67 @@ handlebars_helper!(article_full_url: |blog_url: String, slug: String| {
     String::from(trimmed_blog_url) + "/" + "article/" + &slug
 });
 
-handlebars_helper!(render_tags: |tags: Vec<String>, search_tag: Option<String>| {
-    let mut html = String::from("<ul class=\"tags\">");
-    if let Some(search_tag) = search_tag {
-        for tag in tags {
+// Usage:
+// {{{render_tags tags}}}
+// {{{render_tags tags current_tag}}}
+// Outputs a list of links to the tags' pages, using their display names from `tags.toml`. The
+// current tag, if given, isn't linked. The names can change whenever the articles are rebuilt, so
+// this is registered separately by `CommonData` like `page_links`.
+#[derive(Default)]
+pub struct RenderTags {
+    names: HashMap<String, String>,
+}
+
+impl RenderTags {
+    pub fn new(names: HashMap<String, String>) -> Self {
+        Self { names }
+    }
+
+    fn push_name(&self, html: &mut String, tag: &str) {
+        match self.names.get(tag) {
+            Some(name) => {
+                escape_html(html, name).ok();
+            }
+            None => html.push_str(tag),
+        }
+    }
+}
+
+impl HelperDef for RenderTags {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let tags = h
+            .param(0)
+            .and_then(|p| p.value().as_array())
+            .ok_or_else(|| RenderError::new("render_tags needs a list of tags"))?;
+        let current_tag = h.param(1).and_then(|p| p.value().as_str());
+
+        let mut html = String::from("<ul class=\"tags\">");
+        for tag in tags.iter().filter_map(JsonValue::as_str) {
             html.push_str("<li>");
-            if tag == search_tag {
+            if Some(tag) == current_tag {
                 html.push_str("<span class=\"current-tag\">");
-                html.push_str(&tag);
+                self.push_name(&mut html, tag);
                 html.push_str("</span>");
             } else {
                 html.push_str("<a href=\"/tag/");
-                html.push_str(&tag);
+                escape_href(&mut html, tag).ok();
                 html.push_str("\">");
-                html.push_str(&tag);
+                self.push_name(&mut html, tag);
                 html.push_str("</a>");
             }
             html.push_str("</li>");
         }
         html.push_str("</ul>");
-    } else {
-        for tag in tags {
-            html.push_str("<li><a href=\"/tag/");
-            html.push_str(&tag);
-            html.push_str("\">");
-            html.push_str(&tag);
-            html.push_str("</a></li>");
-        }
-        html.push_str("</ul>");
-    }
 
-    html
-});
+        Ok(ScopedJson::Derived(JsonValue::String(html)))
+    }
+}
 
 handlebars_helper!(return_text: |path: String| {
     let default_text = "Home".to_string();
@@ -190,15 +241,77 @@ handlebars_helper!(asset_path: |filename: String| {
     base_path + &new_filename
 });
 
+// Usage:
+// {{{live_reload_script}}}
+// Outputs a script that reloads the page when content changes, if live reload is enabled.
+handlebars_helper!(live_reload_script: | | {
+    lazy_static! {
+        static ref LIVE_RELOAD: bool = crate::config::Config::load()
+            .map(|c| c.live_reload_enabled())
+            .unwrap_or(false);
+    }
+    if *LIVE_RELOAD {
+        r#"<script>new EventSource("/live_reload").addEventListener("reload", () => location.reload());</script>"#
+    } else {
+        ""
+    }
+});
+
+// Usage:
+// {{{page_links}}}
+// Outputs a list of links to the standalone pages, for navigation menus. This depends on the
+// current list of pages, so is registered separately by `CommonData` whenever that changes.
+pub struct PageLinks {
+    html: String,
+}
+
+impl PageLinks {
+    pub fn new(pages: &[ParsedArticle]) -> Self {
+        let pages: Vec<&ParsedArticle> = pages.iter().filter(|p| !p.draft).collect();
+        if pages.is_empty() {
+            return Self {
+                html: String::new(),
+            };
+        }
+
+        let mut html = String::from("<ul class=\"page-links\">");
+        for page in pages {
+            html.push_str("<li><a href=\"/page/");
+            escape_href(&mut html, &page.slug).ok();
+            html.push_str("\">");
+            escape_html(&mut html, &page.title).ok();
+            html.push_str("</a></li>");
+        }
+        html.push_str("</ul>");
+        Self { html }
+    }
+}
+
+impl HelperDef for PageLinks {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        _: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        Ok(ScopedJson::Derived(JsonValue::String(self.html.clone())))
+    }
+}
+
 pub fn register_helpers(mut hb: Handlebars) -> Handlebars {
     // Not sure there's a way to automate this bit
     hb.register_helper("date_from_timestamp", Box::new(date_from_timestamp));
     hb.register_helper("age_from_timestamp", Box::new(age_from_timestamp));
+    hb.register_helper("in_future", Box::new(in_future));
+    hb.register_helper("reading_time", Box::new(reading_time));
     hb.register_helper("rfc822_date", Box::new(rfc822_date));
     hb.register_helper("article_full_url", Box::new(article_full_url));
     hb.register_helper("return_text", Box::new(return_text));
     hb.register_helper("asset_path", Box::new(asset_path));
-    hb.register_helper("render_tags", Box::new(render_tags));
+    hb.register_helper("render_tags", Box::new(RenderTags::default()));
+    hb.register_helper("live_reload_script", Box::new(live_reload_script));
+    hb.register_helper("page_links", Box::new(PageLinks::new(&[])));
 
     hb
 }
@@ -1,5 +1,6 @@
 use std::fs;
 use std::path::Path;
+use walkdir::WalkDir;
 
 fn should_skip(path: &Path, matching_ext: &str) -> bool {
     if path.is_dir() {
@@ -9,15 +10,58 @@ fn should_skip(path: &Path, matching_ext: &str) -> bool {
     ext.is_none() || ext.unwrap() != matching_ext
 }
 
+// Hidden files and directories, e.g. `.git` or editors' swap files
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Calls `f` with each file in `dir` with the given extension. Entries that can't be read are
+/// logged and skipped.
 pub fn paths_with_ext_in_dir<F>(matching_ext: &str, dir: &Path, mut f: F)
 where
     F: FnMut(&Path),
 {
-    for entry in fs::read_dir(dir).unwrap() {
-        let path = entry.expect("Invalid entry").path();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to read directory {dir:?}: {e:?}");
+            return;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                log::error!("Failed to read entry in {dir:?}: {e:?}");
+                continue;
+            }
+        };
         if should_skip(&path, matching_ext) {
             continue;
         }
         f(&path);
     }
 }
+
+/// Like `paths_with_ext_in_dir`, but also looks in all subdirectories, skipping hidden ones.
+pub fn paths_with_ext_in_tree<F>(matching_ext: &str, dir: &Path, mut f: F)
+where
+    F: FnMut(&Path),
+{
+    let iter = WalkDir::new(dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_hidden(e.path()));
+    for entry in iter {
+        match entry {
+            Ok(entry) => {
+                if !should_skip(entry.path(), matching_ext) {
+                    f(entry.path());
+                }
+            }
+            Err(e) => log::error!("Failed to read entry in {dir:?}: {e}"),
+        }
+    }
+}
@@ -4,9 +4,14 @@ mod commondata;
 mod errors;
 mod handlers;
 mod hb;
+mod rate_limit;
+mod redirects;
 mod routes;
+mod scheduler;
 mod slug;
+mod tags;
 mod typography;
+mod watcher;
 // mod filters;
 mod config;
 mod io;
@@ -53,6 +58,8 @@ async fn main() {
     check_args(&mut config);
 
     let app = routes::init(shared_codata.clone());
+    tokio::spawn(scheduler::publish_scheduled_articles(shared_codata.clone()));
+    watcher::watch_content(shared_codata.clone());
 
     let listen_ip = config
         .listen_ip
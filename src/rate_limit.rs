@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+// Old entries are only cleared out once there are this many, so it doesn't happen on every request
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Limits how many requests each IP address can make in a given length of time.
+pub struct RateLimiter {
+    max_requests: usize,
+    window: Duration,
+    // When each address's current window started, and how many requests it's made in it
+    requests: HashMap<IpAddr, (Instant, usize)>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Records a request from `ip`, returning whether it's within the limit.
+    pub fn allow(&mut self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        if self.requests.len() >= PRUNE_THRESHOLD {
+            let window = self.window;
+            self.requests
+                .retain(|_, (start, _)| now.duration_since(*start) < window);
+        }
+
+        let (start, count) = self.requests.entry(ip).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.max_requests
+    }
+}
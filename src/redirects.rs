@@ -0,0 +1,295 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+pub const REDIRECTS_FILE: &str = "redirects.toml";
+const VALID_STATUSES: [u16; 6] = [301, 302, 303, 307, 308, 410];
+
+fn default_status() -> u16 {
+    301
+}
+
+// A single entry from the `redirects` list. Paths can contain `:name` segments that match any
+// single path segment, and end with a `*name` segment that matches everything remaining; these
+// are substituted into the `to` path by name.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedirectRule {
+    pub from: String,
+    #[serde(default)]
+    pub to: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+}
+
+#[derive(Deserialize)]
+struct RedirectsFile {
+    #[serde(default)]
+    redirects: Vec<RedirectRule>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    CatchAll(String),
+}
+
+// Part of the `to` path, which is either copied as is or replaced with the value captured by one
+// of the segments, by the index of its capture
+#[derive(Clone, Debug)]
+enum Part {
+    Text(String),
+    Capture(usize),
+}
+
+#[derive(Clone, Debug)]
+struct CompiledRule {
+    segments: Vec<Segment>,
+    to: Vec<Part>,
+    status: u16,
+}
+
+impl CompiledRule {
+    fn new(rule: &RedirectRule) -> Option<Self> {
+        if !VALID_STATUSES.contains(&rule.status) {
+            log::error!(
+                "Ignoring redirect from {} with unsupported status {}",
+                rule.from,
+                rule.status
+            );
+            return None;
+        }
+        if rule.to.is_empty() && rule.status != 410 {
+            log::error!("Ignoring redirect from {} with no destination", rule.from);
+            return None;
+        }
+
+        let segments: Vec<Segment> = rule
+            .from
+            .split('/')
+            .map(|s| {
+                if let Some(name) = s.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = s.strip_prefix('*') {
+                    Segment::CatchAll(name.to_string())
+                } else {
+                    Segment::Literal(s.to_string())
+                }
+            })
+            .collect();
+        let names: Vec<&str> = segments
+            .iter()
+            .filter_map(|s| match s {
+                Segment::Param(name) | Segment::CatchAll(name) => Some(name.as_str()),
+                Segment::Literal(_) => None,
+            })
+            .collect();
+        Some(Self {
+            to: parse_to(&rule.to, &names),
+            segments,
+            status: rule.status,
+        })
+    }
+
+    // Returns the destination path with any captured segments substituted in, if `path` matches.
+    fn apply(&self, path: &str) -> Option<String> {
+        let mut parts = path.split('/');
+        let mut captures: Vec<String> = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => {
+                    if parts.next() != Some(s) {
+                        return None;
+                    }
+                }
+                Segment::Param(_) => match parts.next() {
+                    Some(p) if !p.is_empty() => captures.push(p.to_string()),
+                    _ => return None,
+                },
+                Segment::CatchAll(_) => {
+                    captures.push(parts.by_ref().collect::<Vec<&str>>().join("/"));
+                }
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let to: String = self
+            .to
+            .iter()
+            .map(|part| match part {
+                Part::Text(s) => s.as_str(),
+                Part::Capture(i) => captures[*i].as_str(),
+            })
+            .collect();
+        // Captured values can start with slashes, e.g. `/old//example.com` with a catch-all, which
+        // browsers would take as a link to another site
+        if to.starts_with(['/', '\\']) {
+            return Some(format!("/{}", to.trim_start_matches(['/', '\\'])));
+        }
+        Some(to)
+    }
+}
+
+// Splits the `to` path into text and the `:name` or `*name` references to captured segments. The
+// longest name that fits is used, so e.g. `:slug` doesn't take part of `:slug_prefix`.
+fn parse_to(to: &str, names: &[&str]) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = to;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        let capture = if c == ':' || c == '*' {
+            names
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| !name.is_empty() && after.starts_with(**name))
+                .max_by_key(|(_, name)| name.len())
+        } else {
+            None
+        };
+        match capture {
+            Some((i, name)) => {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Capture(i));
+                rest = &after[name.len()..];
+            }
+            None => {
+                text.push(c);
+                rest = after;
+            }
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    parts
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Redirects {
+    rules: Vec<CompiledRule>,
+}
+
+impl Redirects {
+    // Combines the redirects listed in the config file with any in `redirects.toml` in the content
+    // directory. The first matching rule wins, with the config file's rules checked first.
+    pub fn load(config: &Config) -> Self {
+        let mut rules = config.redirects.clone();
+        let filename = PathBuf::from(&config.content_dir).join(REDIRECTS_FILE);
+        if let Ok(s) = fs::read_to_string(&filename) {
+            match toml::from_str::<RedirectsFile>(&s) {
+                Ok(file) => rules.extend(file.redirects),
+                Err(e) => log::error!("Failed to parse redirects from {:?}: {:?}", filename, e),
+            }
+        }
+
+        Self {
+            rules: rules.iter().filter_map(CompiledRule::new).collect(),
+        }
+    }
+
+    /// Finds the first rule matching `path`, returning its status and destination.
+    pub fn find(&self, path: &str) -> Option<(u16, String)> {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.apply(path).map(|to| (rule.status, to)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirects(rules: &[(&str, &str, u16)]) -> Redirects {
+        let rules: Vec<RedirectRule> = rules
+            .iter()
+            .map(|(from, to, status)| RedirectRule {
+                from: from.to_string(),
+                to: to.to_string(),
+                status: *status,
+            })
+            .collect();
+        Redirects {
+            rules: rules.iter().filter_map(CompiledRule::new).collect(),
+        }
+    }
+
+    fn find(redirects: &Redirects, path: &str) -> Option<String> {
+        redirects.find(path).map(|(_, to)| to)
+    }
+
+    #[test]
+    fn literal_rule() {
+        let r = redirects(&[("/about-me", "/page/about", 301)]);
+        assert_eq!(find(&r, "/about-me").as_deref(), Some("/page/about"));
+        assert_eq!(find(&r, "/about-me/more"), None);
+        assert_eq!(find(&r, "/about"), None);
+    }
+
+    #[test]
+    fn param_rule() {
+        let r = redirects(&[("/articles/:slug", "/article/:slug", 308)]);
+        assert_eq!(
+            r.find("/articles/some-post"),
+            Some((308, "/article/some-post".to_string()))
+        );
+        assert_eq!(find(&r, "/articles/"), None);
+        assert_eq!(find(&r, "/articles/a/b"), None);
+    }
+
+    #[test]
+    fn captured_values_not_substituted_again() {
+        let r = redirects(&[("/:year/:slug", "/archive/:slug/:year", 301)]);
+        assert_eq!(
+            find(&r, "/:slug/:year").as_deref(),
+            Some("/archive/:year/:slug")
+        );
+    }
+
+    #[test]
+    fn longest_name_used() {
+        let r = redirects(&[("/:slug/:slug_prefix", "/:slug_prefix-:slug", 301)]);
+        assert_eq!(find(&r, "/a/b").as_deref(), Some("/b-a"));
+    }
+
+    #[test]
+    fn catch_all_rule() {
+        let r = redirects(&[("/old/*rest", "/new/*rest", 301)]);
+        assert_eq!(find(&r, "/old/a/b/c").as_deref(), Some("/new/a/b/c"));
+        assert_eq!(find(&r, "/old/").as_deref(), Some("/new/"));
+        assert_eq!(find(&r, "/other/a"), None);
+    }
+
+    #[test]
+    fn no_redirects_to_other_sites() {
+        let r = redirects(&[("/old/*rest", "/*rest", 301)]);
+        assert_eq!(find(&r, "/old//evil.com").as_deref(), Some("/evil.com"));
+        assert_eq!(find(&r, "/old/\\evil.com").as_deref(), Some("/evil.com"));
+        let r = redirects(&[("/go/*rest", "https://example.com/*rest", 302)]);
+        assert_eq!(find(&r, "/go/a").as_deref(), Some("https://example.com/a"));
+    }
+
+    #[test]
+    fn gone_and_invalid_rules() {
+        let r = redirects(&[
+            ("/removed", "", 410),
+            ("/bad", "/x", 200),
+            ("/none", "", 301),
+        ]);
+        assert_eq!(r.find("/removed"), Some((410, String::new())));
+        assert_eq!(r.find("/bad"), None);
+        assert_eq!(r.find("/none"), None);
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let r = redirects(&[("/a/:x", "/first/:x", 301), ("/a/*x", "/second/*x", 301)]);
+        assert_eq!(find(&r, "/a/b").as_deref(), Some("/first/b"));
+        assert_eq!(find(&r, "/a/b/c").as_deref(), Some("/second/b/c"));
+    }
+}
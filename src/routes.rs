@@ -1,7 +1,7 @@
 use axum::{
-    extract::{DefaultBodyLimit, Path},
+    extract::DefaultBodyLimit,
     http::StatusCode,
-    response::{IntoResponse, Redirect},
+    response::IntoResponse,
     routing::{delete, get, get_service, post, put},
     Router,
 };
@@ -13,13 +13,20 @@ use tower_http::{compression::CompressionLayer, services::ServeDir, timeout::Tim
 use crate::handlers::{
     admin::{
         admin_page_handler, check_thumb_progress, create_article_handler, delete_article_handler,
-        delete_image_handler, do_login_handler, do_logout_handler, image_list_handler,
-        login_page_handler, rebuild_index_handler, update_article_handler, upload_image_handler,
+        delete_image_handler, delete_unused_images_handler, do_login_handler, do_logout_handler,
+        image_list_handler, login_page_handler, rebuild_index_handler, rename_tags_handler,
+        update_article_handler, upload_image_handler,
     },
     article::{article_handler, article_text_handler},
     comment::comment_handler,
-    index::{home_handler, index_handler, rss_handler, tag_handler, tag_home_handler},
+    index::{
+        home_handler, index_handler, rss_handler, tag_handler, tag_home_handler, tags_handler,
+    },
+    live_reload::live_reload_handler,
     not_found_handler,
+    page::page_handler,
+    search::{api_search_handler, search_handler},
+    series::series_handler,
     static_files::asset_handler,
 };
 
@@ -46,20 +53,24 @@ pub fn init(shared_data: SharedData) -> Router {
         .route("/images", post(upload_image_handler))
         .layer(DefaultBodyLimit::max(25 * 1024 * 1024));
 
+    // Event streams stay open indefinitely, so are kept clear of the compression and timeout
+    // middleware, which would hold back or cut off events.
+    let live_reload_route = Router::new()
+        .route("/live_reload", get(live_reload_handler))
+        .with_state(shared_data.clone());
+
     Router::new()
         .route("/", get(home_handler))
         .route("/articles/:page_or_slug", get(index_handler))
         .route("/article/:slug", get(article_handler))
         .route("/article/:slug/text", get(article_text_handler))
+        .route("/page/:slug", get(page_handler))
+        .route("/series/:slug", get(series_handler))
+        .route("/search", get(search_handler))
+        .route("/api/search", get(api_search_handler))
+        .route("/tags", get(tags_handler))
         .route("/tag/:tag", get(tag_home_handler))
         .route("/tag/:tag/:page", get(tag_handler))
-        .route(
-            "/tag/:tag/",
-            get(|Path(tag): Path<String>| async move {
-                // Legacy tag support, since my blog still gets hits on this path
-                Redirect::permanent(&(String::from("/tag/") + &tag))
-            }),
-        )
         .route("/rss", get(rss_handler))
         .route("/comment/:slug", post(comment_handler))
         .route("/login", get(login_page_handler))
@@ -67,6 +78,7 @@ pub fn init(shared_data: SharedData) -> Router {
         .route("/logout", post(do_logout_handler))
         .route("/admin", get(admin_page_handler))
         .route("/rebuild_index", post(rebuild_index_handler))
+        .route("/rename_tags", post(rename_tags_handler))
         .route("/articles", post(create_article_handler))
         .route("/article/:slug", put(update_article_handler))
         .route("/article/:slug", delete(delete_article_handler))
@@ -74,10 +86,12 @@ pub fn init(shared_data: SharedData) -> Router {
         .merge(img_upload_route)
         .route("/check_thumb_progress", get(check_thumb_progress))
         .route("/images/*path", delete(delete_image_handler))
+        .route("/unused_images", delete(delete_unused_images_handler))
         .route("/assets/*path", get(asset_handler))
         .nest_service("/content/images/", image_dir_service)
+        .fallback(not_found_handler)
         .with_state(shared_data)
         .layer(CookieManagerLayer::new())
         .layer(middleware)
-        .fallback(not_found_handler)
+        .merge(live_reload_route)
 }
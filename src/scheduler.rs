@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use crate::article::storage::next_scheduled;
+use crate::commondata::rebuild;
+use crate::handlers::create_timestamp;
+use crate::SharedData;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Periodically checks whether any scheduled articles have reached their publication time, so they
+// get linked in with the rest without having to rebuild the index by hand.
+pub async fn publish_scheduled_articles(data: SharedData) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if data.read().has_due_articles() {
+            log::info!("Publishing scheduled articles");
+            publish_due_articles(&data);
+        }
+    }
+}
+
+// Newly published articles are already included in index pages as soon as their time comes, but
+// they still need to be linked in with their neighbours, and wiki links to them resolved, which
+// means starting again from the articles as they were before linking.
+fn publish_due_articles(data: &SharedData) {
+    if let Err(e) = rebuild(data) {
+        log::error!("Failed to rebuild index to publish scheduled articles: {e:?}");
+        let mut data = data.write();
+        data.next_scheduled = next_scheduled(&data.articles, create_timestamp());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::article::builder::ParsedArticle;
+use crate::config::Config;
+use crate::slug::Slug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+pub const TAGS_FILE: &str = "tags.toml";
+
+// Extra details about a tag, from its table in `tags.toml`, e.g.
+//
+// [ios]
+// name = "iOS"
+// description = "Apple's mobile operating system"
+// weight = -1
+// aliases = ["iphone-os"]
+// parent = "apple"
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TagInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    // Tags are listed in order of weight, lowest first, and then by how many articles they have
+    pub weight: i64,
+    // Other tags that mean the same thing, which are replaced with this one wherever they're used
+    pub aliases: Vec<String>,
+    // A broader tag, whose page also lists the articles with this one
+    pub parent: Option<String>,
+}
+
+/// Maps tags to the tag they're an alias of, for folding synonyms together when articles are
+/// built.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TagAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl TagAliases {
+    /// The tag that `tag` is an alias of, or `tag` itself if it isn't one.
+    pub fn canonical<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.aliases.get(tag).map(String::as_str).unwrap_or(tag)
+    }
+
+    pub fn is_alias(&self, tag: &str) -> bool {
+        self.aliases.contains_key(tag)
+    }
+}
+
+/// A tag to link to, with the name to show for it.
+#[derive(Serialize, Debug)]
+pub struct TagLink<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TagMetadata {
+    tags: HashMap<String, TagInfo>,
+    aliases: TagAliases,
+    children: HashMap<String, Vec<String>>,
+}
+
+/// A tag with its details and the number of published articles with it, for the tag list.
+#[derive(Serialize, Debug)]
+pub struct TagSummary<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+    pub count: usize,
+}
+
+impl TagMetadata {
+    // The file is optional, so it not existing isn't an error. Tags are keyed by their slug, the
+    // same as in articles, so e.g. `[iOS]` applies to the `ios` tag.
+    pub fn load(config: &Config) -> Self {
+        let filename = PathBuf::from(&config.content_dir).join(TAGS_FILE);
+        let tags: HashMap<String, TagInfo> = match fs::read_to_string(&filename) {
+            Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+                log::error!("Failed to parse tags from {:?}: {:?}", filename, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        let mut tags: HashMap<String, TagInfo> = tags
+            .into_iter()
+            .map(|(tag, mut info)| {
+                info.parent = info.parent.map(|p| Slug::new(&p).into());
+                (Slug::new(&tag).into(), info)
+            })
+            .collect();
+
+        // An alias can't also be a tag in its own right, or be shared by two tags
+        let mut aliases = TagAliases::default();
+        for (tag, info) in &tags {
+            for alias in &info.aliases {
+                let alias: String = Slug::new(alias).into();
+                if alias == *tag {
+                    continue;
+                }
+                if tags.contains_key(&alias) {
+                    log::warn!("Ignoring alias {alias} of tag {tag}, as it's a tag itself");
+                } else if let Some(other) = aliases.aliases.get(&alias) {
+                    log::warn!(
+                        "Ignoring alias {alias} of tag {tag}, as it's already an alias of {other}"
+                    );
+                } else {
+                    aliases.aliases.insert(alias, tag.clone());
+                }
+            }
+        }
+
+        // Parents given by an alias mean the tag it's an alias of
+        for info in tags.values_mut() {
+            if let Some(parent) = &info.parent {
+                info.parent = Some(aliases.canonical(parent).to_string());
+            }
+        }
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for (tag, info) in &tags {
+            if let Some(parent) = &info.parent {
+                children
+                    .entry(parent.clone())
+                    .or_default()
+                    .push(tag.clone());
+            }
+        }
+        for list in children.values_mut() {
+            list.sort();
+        }
+
+        Self {
+            tags,
+            aliases,
+            children,
+        }
+    }
+
+    pub fn aliases(&self) -> &TagAliases {
+        &self.aliases
+    }
+
+    pub fn parent(&self, tag: &str) -> Option<&str> {
+        self.get(tag).and_then(|info| info.parent.as_deref())
+    }
+
+    pub fn children(&self, tag: &str) -> &[String] {
+        self.children.get(tag).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The tag along with its children, their children, and so on. A tag page lists the articles
+    /// with any of these.
+    pub fn with_descendants<'a>(&'a self, tag: &'a str) -> HashSet<&'a str> {
+        let mut found = HashSet::from([tag]);
+        let mut pending = vec![tag];
+        while let Some(tag) = pending.pop() {
+            for child in self.children(tag) {
+                // A tag can end up being its own ancestor if the file is muddled up
+                if found.insert(child) {
+                    pending.push(child);
+                }
+            }
+        }
+        found
+    }
+
+    /// The tag along with its parent, its parent's parent, and so on.
+    pub fn with_ancestors<'a>(&'a self, tag: &'a str) -> HashSet<&'a str> {
+        let mut found = HashSet::from([tag]);
+        let mut current = tag;
+        while let Some(parent) = self.parent(current) {
+            if !found.insert(parent) {
+                break;
+            }
+            current = parent;
+        }
+        found
+    }
+
+    pub fn link<'a>(&'a self, tag: &'a str) -> TagLink<'a> {
+        TagLink {
+            slug: tag,
+            name: self.display_name(tag),
+        }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&TagInfo> {
+        self.tags.get(tag)
+    }
+
+    /// The name to show for a tag, which is just the tag itself unless it's been given one.
+    pub fn display_name<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.get(tag)
+            .and_then(|info| info.name.as_deref())
+            .unwrap_or(tag)
+    }
+
+    /// Display names for all the tags that have one.
+    pub fn display_names(&self) -> HashMap<String, String> {
+        self.tags
+            .iter()
+            .filter_map(|(tag, info)| info.name.clone().map(|name| (tag.clone(), name)))
+            .collect()
+    }
+
+    /// Every tag used by the given articles, with how many of them use it, sorted by weight and
+    /// then by count. Articles count towards their tags' parents too, as they're listed on the
+    /// parents' pages.
+    pub fn summarize<'a>(&'a self, articles: &[&'a ParsedArticle]) -> Vec<TagSummary<'a>> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for article in articles {
+            let tags: HashSet<&str> = article
+                .tags
+                .iter()
+                .flat_map(|t| self.with_ancestors(t))
+                .collect();
+            for tag in tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+
+        let mut summaries: Vec<TagSummary> = counts
+            .into_iter()
+            .map(|(slug, count)| TagSummary {
+                slug,
+                name: self.display_name(slug),
+                description: self.get(slug).and_then(|info| info.description.as_deref()),
+                count,
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            let weight = |s: &TagSummary| self.get(s.slug).map(|i| i.weight).unwrap_or(0);
+            weight(a)
+                .cmp(&weight(b))
+                .then(b.count.cmp(&a.count))
+                .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        summaries
+    }
+}
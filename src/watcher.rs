@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebouncedEvent};
+use regex::Regex;
+
+use crate::article::aliases::SLUG_ALIASES_FILE;
+use crate::commondata::rebuild;
+use crate::handlers::static_files::refresh_js_manifest;
+use crate::hb::try_create_handlebars;
+use crate::redirects::REDIRECTS_FILE;
+use crate::tags::TAGS_FILE;
+use crate::SharedData;
+
+// Editors and git tend to touch several files at once, so changes are gathered up for this long
+// before doing anything about them.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Files in the top level of the content directory that are read when the index is rebuilt
+const INDEX_FILES: [&str; 3] = [TAGS_FILE, REDIRECTS_FILE, SLUG_ALIASES_FILE];
+
+// What needs doing about a batch of changes
+#[derive(Default)]
+struct Changes {
+    articles: bool,
+    templates: bool,
+    assets: bool,
+}
+
+impl Changes {
+    fn any(&self) -> bool {
+        self.articles || self.templates || self.assets
+    }
+}
+
+struct WatchedDirs {
+    content: PathBuf,
+    articles: PathBuf,
+    pages: PathBuf,
+    templates: PathBuf,
+    assets: PathBuf,
+}
+
+impl WatchedDirs {
+    fn new(content_dir: &str) -> Self {
+        // Event paths are absolute, so the directories need to be as well to compare with them
+        let content_dir = Path::new(content_dir)
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from(content_dir));
+        Self {
+            content: content_dir.clone(),
+            articles: content_dir.join("articles"),
+            pages: content_dir.join("pages"),
+            templates: content_dir.join("templates"),
+            assets: content_dir.join("assets"),
+        }
+    }
+
+    fn is_index_file(&self, path: &Path) -> bool {
+        path.parent() == Some(self.content.as_path())
+            && path
+                .file_name()
+                .is_some_and(|name| INDEX_FILES.iter().any(|f| name == *f))
+    }
+
+    fn classify(&self, events: &[DebouncedEvent]) -> Changes {
+        let mut changes = Changes::default();
+        for event in events {
+            let path = &event.path;
+            // Pages are rebuilt along with articles
+            if path.starts_with(&self.articles)
+                || path.starts_with(&self.pages)
+                || self.is_index_file(path)
+            {
+                changes.articles = true;
+            } else if path.starts_with(&self.templates) {
+                changes.templates = true;
+            } else if path.starts_with(&self.assets) && !is_manifest(path) {
+                changes.assets = true;
+            }
+        }
+        changes
+    }
+}
+
+// The JS manifest and its compiled copies, which change whenever it's recompiled
+fn is_manifest(path: &Path) -> bool {
+    lazy_static! {
+        static ref MANIFEST: Regex = Regex::new(r"^manifest(-\d{14})?\.js$").unwrap();
+    }
+    path.file_name()
+        .map(|f| MANIFEST.is_match(&f.to_string_lossy()))
+        .unwrap_or(false)
+}
+
+fn apply_changes(changes: Changes, dirs: &WatchedDirs, data: &SharedData) {
+    if !changes.any() {
+        return;
+    }
+
+    if changes.articles {
+        log::info!("Articles changed, rebuilding index");
+        if let Err(e) = rebuild(data) {
+            log::error!("Failed to rebuild article index: {e:?}");
+        }
+    }
+
+    if changes.templates {
+        log::info!("Templates changed, reloading");
+        // Templates are parsed before taking the lock, and the current ones are kept if any fail
+        let config = data.read().config.clone();
+        match try_create_handlebars(&config) {
+            Ok(hbs) => {
+                let mut data = data.write();
+                data.hbs = hbs;
+                data.register_content_helpers();
+            }
+            Err(e) => log::error!("Failed to reload templates, keeping previous ones: {e}"),
+        }
+    }
+
+    // The manifest is compiled from the assets, so is recompiled whatever changed, which also
+    // gives it a new timestamp
+    if changes.assets {
+        log::info!("Assets changed, recompiling JS manifest");
+        refresh_js_manifest(&dirs.assets.join("js").join("manifest.js"));
+    }
+
+    // Nobody might be listening, which is fine
+    data.read().reload_tx.send(()).ok();
+}
+
+/// Starts watching the content directory in a background thread, if enabled in the config. Live
+/// reload relies on the watcher, so enabling that enables this too.
+pub fn watch_content(data: SharedData) {
+    let (enabled, content_dir) = {
+        let data = data.read();
+        let config = &data.config;
+        (
+            config.watch_content || config.live_reload_enabled(),
+            config.content_dir.clone(),
+        )
+    };
+    if !enabled {
+        return;
+    }
+
+    let dirs = WatchedDirs::new(&content_dir);
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = match new_debouncer(DEBOUNCE_TIMEOUT, tx) {
+        Ok(d) => d,
+        Err(e) => {
+            log::error!("Failed to create content watcher: {e:?}");
+            return;
+        }
+    };
+    for dir in [&dirs.articles, &dirs.pages, &dirs.templates, &dirs.assets] {
+        if !dir.is_dir() {
+            continue;
+        }
+        if let Err(e) = debouncer.watcher().watch(dir, RecursiveMode::Recursive) {
+            log::error!("Failed to watch {dir:?} for changes: {e:?}");
+        }
+    }
+    // Only the top level, for the files read when rebuilding, which might not exist yet
+    if let Err(e) = debouncer
+        .watcher()
+        .watch(&dirs.content, RecursiveMode::NonRecursive)
+    {
+        log::error!("Failed to watch {:?} for changes: {e:?}", dirs.content);
+    }
+    log::info!("Watching {content_dir} for changes");
+
+    thread::spawn(move || {
+        // The debouncer stops watching when dropped, so it has to live as long as the thread
+        let _debouncer = debouncer;
+        for result in rx {
+            match result {
+                Ok(events) => apply_changes(dirs.classify(&events), &dirs, &data),
+                Err(e) => log::error!("Content watcher error: {e:?}"),
+            }
+        }
+    });
+}